@@ -0,0 +1,11 @@
+-- every permission overwrite the bot applied because of an exclusion
+CREATE TABLE exclusion_overwrites (
+    guild_id INTEGER NOT NULL,
+    user_1 INTEGER NOT NULL,
+    user_2 INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    permission INTEGER NOT NULL,
+    -- if the user was allowed the denied permission before, it's allowed again once it's revoked
+    allowed INTEGER NOT NULL DEFAULT 0
+)
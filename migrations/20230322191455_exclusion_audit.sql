@@ -0,0 +1,17 @@
+-- per guild settings
+CREATE TABLE guild_settings (
+    guild_id INTEGER PRIMARY KEY NOT NULL,
+    moderator_role INTEGER
+);
+
+-- every change made to the exclusions of a guild
+CREATE TABLE exclusion_audit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    guild_id INTEGER NOT NULL,
+    actor_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    user_1 INTEGER NOT NULL,
+    user_2 INTEGER NOT NULL,
+    reason TEXT,
+    created_at INTEGER NOT NULL
+);
@@ -0,0 +1,42 @@
+-- exclusions get an id, a reason and an optional expiry.
+-- pairs are stored ordered (user_1 < user_2) so (a,b) and (b,a) are the same exclusion
+CREATE TABLE exclusions_v2 (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    guild_id INTEGER NOT NULL,
+    user_1 INTEGER NOT NULL,
+    user_2 INTEGER NOT NULL,
+    reason TEXT,
+    expires_at INTEGER
+);
+
+INSERT INTO exclusions_v2 (guild_id, user_1, user_2)
+    SELECT DISTINCT guild_id, MIN(user_1, user_2), MAX(user_1, user_2)
+    FROM exclusions
+    WHERE user_1 != user_2;
+
+DROP TABLE exclusions;
+ALTER TABLE exclusions_v2 RENAME TO exclusions;
+CREATE UNIQUE INDEX exclusions_pair ON exclusions (guild_id, user_1, user_2);
+
+-- overwrites now reference the exclusion by its id
+CREATE TABLE exclusion_overwrites_v2 (
+    exclusion_id INTEGER NOT NULL,
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    permission INTEGER NOT NULL,
+    allowed INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT INTO exclusion_overwrites_v2 (exclusion_id, guild_id, channel_id, user_id, permission, allowed)
+    SELECT DISTINCT exclusions.id, overwrites.guild_id, overwrites.channel_id, overwrites.user_id, overwrites.permission, overwrites.allowed
+    FROM exclusion_overwrites AS overwrites
+    JOIN exclusions
+        ON exclusions.guild_id = overwrites.guild_id
+        AND exclusions.user_1 = MIN(overwrites.user_1, overwrites.user_2)
+        AND exclusions.user_2 = MAX(overwrites.user_1, overwrites.user_2);
+
+DROP TABLE exclusion_overwrites;
+ALTER TABLE exclusion_overwrites_v2 RENAME TO exclusion_overwrites;
+
+UPDATE exclusion_audit SET user_1 = MIN(user_1, user_2), user_2 = MAX(user_1, user_2);
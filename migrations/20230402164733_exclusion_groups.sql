@@ -0,0 +1,23 @@
+-- named groups where every member is excluded from every other member
+CREATE TABLE exclusion_groups (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    guild_id INTEGER NOT NULL,
+    name TEXT NOT NULL
+);
+CREATE UNIQUE INDEX exclusion_groups_name ON exclusion_groups (guild_id, name);
+
+-- members of a group are either users or roles
+CREATE TABLE exclusion_group_members (
+    group_id INTEGER NOT NULL,
+    member_id INTEGER NOT NULL,
+    kind TEXT NOT NULL
+);
+CREATE UNIQUE INDEX exclusion_group_members_unique ON exclusion_group_members (group_id, member_id);
+
+-- overwrites can be applied because of a pair or a group
+ALTER TABLE exclusion_overwrites RENAME COLUMN exclusion_id TO source_id;
+ALTER TABLE exclusion_overwrites ADD COLUMN source TEXT NOT NULL DEFAULT 'pair';
+
+-- changes of a group are audited with its name, `user_1` is the changed member of the kind, if any
+ALTER TABLE exclusion_audit ADD COLUMN group_name TEXT;
+ALTER TABLE exclusion_audit ADD COLUMN member_kind TEXT;
@@ -0,0 +1,2 @@
+-- the user that ignores the other one, mutual exclusions have none
+ALTER TABLE exclusions ADD COLUMN ignoring_user INTEGER;
@@ -0,0 +1,17 @@
+-- how text channels are handled by the exclusions, see `util::TextPolicy`
+ALTER TABLE guild_settings ADD COLUMN text_policy TEXT NOT NULL DEFAULT 'ignore';
+ALTER TABLE guild_settings ADD COLUMN activity_minutes INTEGER NOT NULL DEFAULT 60;
+
+-- last message of a user in a text channel
+CREATE TABLE channel_activity (
+    channel_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    last_message_at INTEGER NOT NULL,
+    PRIMARY KEY (channel_id, user_id)
+);
+
+-- text channels that opted in to the exclusions
+CREATE TABLE exclusion_channels (
+    channel_id INTEGER PRIMARY KEY NOT NULL,
+    guild_id INTEGER NOT NULL
+);
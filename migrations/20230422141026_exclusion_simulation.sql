@@ -0,0 +1,2 @@
+-- channel simulated exclusion overwrites are logged to, the guild isn't simulated if it is null
+ALTER TABLE guild_settings ADD COLUMN simulate_channel INTEGER;
@@ -0,0 +1,6 @@
+-- last known mensa plan of every place, so the plans survive restarts
+CREATE TABLE mensa_plans (
+    place TEXT PRIMARY KEY NOT NULL,
+    plan TEXT NOT NULL,
+    fetched_at INTEGER NOT NULL
+);
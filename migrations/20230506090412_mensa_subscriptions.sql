@@ -0,0 +1,10 @@
+-- channels that get the mensa plan posted every weekday
+CREATE TABLE mensa_subscriptions (
+    channel_id INTEGER PRIMARY KEY NOT NULL,
+    guild_id INTEGER NOT NULL,
+    place TEXT NOT NULL,
+    -- local time in Europe/Berlin as HH:MM
+    post_time TEXT NOT NULL,
+    -- local date of the last post as YYYY-MM-DD
+    last_posted TEXT
+);
@@ -0,0 +1,7 @@
+-- diet preferences of the users for the mensa plans
+CREATE TABLE mensa_preferences (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    diet TEXT NOT NULL DEFAULT 'all',
+    -- comma separated allergen codes
+    excluded_allergens TEXT NOT NULL DEFAULT ''
+);
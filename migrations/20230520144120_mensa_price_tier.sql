@@ -0,0 +1,2 @@
+-- price tier the user pays, all tiers are shown if it is null
+ALTER TABLE mensa_preferences ADD COLUMN price_tier TEXT;
@@ -0,0 +1,3 @@
+-- the mensa that is shown by default, the user place overrides the guild place
+ALTER TABLE guild_settings ADD COLUMN mensa_place TEXT;
+ALTER TABLE mensa_preferences ADD COLUMN place TEXT;
@@ -0,0 +1,13 @@
+-- ratings of the mensa dishes, a user can rate a dish once per day
+CREATE TABLE dish_ratings (
+    user_id INTEGER NOT NULL,
+    place TEXT NOT NULL,
+    -- normalized name of the dish, see `Dish::key`
+    dish TEXT NOT NULL,
+    name TEXT NOT NULL,
+    -- the day the dish was served as YYYY-MM-DD
+    served_on TEXT NOT NULL,
+    rating INTEGER NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    PRIMARY KEY (user_id, place, dish, served_on)
+);
+CREATE INDEX dish_ratings_dish ON dish_ratings (place, dish);
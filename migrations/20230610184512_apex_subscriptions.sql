@@ -0,0 +1,12 @@
+-- channels that get a message when the rotation switches to a map
+CREATE TABLE apex_subscriptions (
+    channel_id INTEGER NOT NULL,
+    guild_id INTEGER NOT NULL,
+    -- name of the map without case, spaces or apostrophes, see `map_key`
+    map TEXT NOT NULL,
+    -- the mode of the rotation, see `MapMode::key`
+    mode TEXT NOT NULL,
+    -- the role or user mentions of the message, if any
+    mentions TEXT,
+    PRIMARY KEY (channel_id, map, mode)
+);
@@ -0,0 +1,7 @@
+-- the apex legends accounts that discord users linked to themselves
+CREATE TABLE apex_accounts (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    -- see `Platform::key`
+    platform TEXT NOT NULL,
+    player TEXT NOT NULL
+);
@@ -0,0 +1,1021 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    sync::Arc,
+    time::{Duration as StdDuration, Instant},
+};
+
+use crate::{apex_api, apex_client, error::Error as AYError, util, Result};
+use apex_rs::model::Map;
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude as serenity;
+use serde::{de::DeserializeOwned, Deserialize};
+use tokio::sync::{Mutex, RwLock};
+
+/// the apex api that apex-rs talks to, the endpoints are appended to it
+const API_URL: &str = "https://api.mozambiquehe.re";
+/// how long responses other than the map rotation are cached, if not configured
+pub const DEFAULT_TTL_SECONDS: i64 = 300;
+/// how many requests are sent to the apex api per second, if not configured
+pub const DEFAULT_RATE_LIMIT: f64 = 2.0;
+/// how long a request waits for the rate limit before it gives up
+const MAX_RATE_LIMIT_WAIT: StdDuration = StdDuration::from_secs(3);
+/// how long an expired response is used if the api can't be reached, older ones are dropped
+const MAX_STALE_MINUTES: i64 = 30;
+/// how long to wait if the rotation couldn't be requested
+const RETRY_DELAY_MINUTES: i64 = 5;
+/// how long to wait if the api still has the old rotation after it ended
+const ROTATION_DELAY_SECONDS: i64 = 30;
+
+/// the game modes that have their own map rotation
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, poise::ChoiceParameter)]
+pub enum MapMode {
+    #[name = "battle royale"]
+    BattleRoyal,
+    #[name = "ranked"]
+    Ranked,
+    #[name = "arenas/mixtape"]
+    Arenas,
+    #[name = "limited time mode"]
+    Ltm,
+    /// not a mode, stands for every mode at once
+    #[name = "all"]
+    All,
+}
+
+impl MapMode {
+    /// all modes with a rotation, in the order they are shown
+    pub const MODES: [MapMode; 4] = [
+        MapMode::BattleRoyal,
+        MapMode::Ranked,
+        MapMode::Arenas,
+        MapMode::Ltm,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MapMode::BattleRoyal => "battle royale",
+            MapMode::Ranked => "ranked",
+            MapMode::Arenas => "arenas/mixtape",
+            MapMode::Ltm => "limited time mode",
+            MapMode::All => "all modes",
+        }
+    }
+
+    /// how the mode is stored in the database
+    pub fn key(&self) -> &'static str {
+        match self {
+            MapMode::BattleRoyal => "battle_royal",
+            MapMode::Ranked => "ranked",
+            MapMode::Arenas => "arenas",
+            MapMode::Ltm => "ltm",
+            MapMode::All => "all",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<MapMode> {
+        MapMode::MODES
+            .into_iter()
+            .chain([MapMode::All])
+            .find(|mode| mode.key() == key)
+    }
+}
+
+/// the map a mode is currently played on
+pub struct CurrentMap {
+    pub mode: MapMode,
+    pub name: String,
+    pub end: DateTime<Utc>,
+    pub embed: serenity::CreateEmbed,
+}
+
+impl CurrentMap {
+    fn of(rotation: &ModeRotation) -> Option<CurrentMap> {
+        let map = rotation.current.as_ref()?;
+        let mut embed = util::embed_map(map, false);
+        embed.title(rotation.mode.name());
+        Some(CurrentMap {
+            mode: rotation.mode,
+            name: map.name().to_string(),
+            end: map.end_as_date(),
+            embed,
+        })
+    }
+}
+
+/// the name of a map without case, spaces or apostrophes, so "worlds edge" is World's Edge.
+/// any name can be subscribed to, so maps that are added to the game later work as well
+fn map_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// a channel that gets a message when `mode` switches to `map`
+pub struct MapSubscription {
+    pub channel_id: u64,
+    pub mentions: Option<String>,
+}
+
+/// subscribes the channel to the map, it's compared with the rotation by its [`map_key`]
+pub async fn subscribe_map(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    channel_id: i64,
+    map: &str,
+    mode: MapMode,
+    mentions: Option<&str>,
+) -> Result<()> {
+    let map = map_key(map);
+    if map.is_empty() {
+        return Err(Box::new(AYError::InvalidInput("the map needs a name")));
+    }
+    let mode = mode.key();
+    sqlx::query!(
+        "INSERT INTO apex_subscriptions (channel_id, guild_id, map, mode, mentions) VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT(channel_id, map, mode) DO UPDATE SET mentions = excluded.mentions",
+        channel_id,
+        guild_id,
+        map,
+        mode,
+        mentions,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// removes the subscription of the map, of every mode if there is none
+pub async fn unsubscribe_map(
+    database: &sqlx::SqlitePool,
+    channel_id: i64,
+    map: &str,
+    mode: Option<MapMode>,
+) -> Result<()> {
+    let map = map_key(map);
+    let mode = mode.map(|mode| mode.key());
+    let result = sqlx::query!(
+        "DELETE FROM apex_subscriptions WHERE channel_id = ? AND map = ? AND (? IS NULL OR mode = ?)",
+        channel_id,
+        map,
+        mode,
+        mode,
+    )
+    .execute(database)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(Box::new(AYError::InvalidInput(
+            "this channel isn't subscribed to that map",
+        )));
+    }
+    Ok(())
+}
+
+/// all channels that want to know when `mode` switches to `map`
+async fn get_map_subscriptions(
+    database: &sqlx::SqlitePool,
+    map: &str,
+    mode: MapMode,
+) -> Result<Vec<MapSubscription>> {
+    let map = map_key(map);
+    let mode = mode.key();
+    let all = MapMode::All.key();
+    Ok(sqlx::query!(
+        "SELECT channel_id, mentions FROM apex_subscriptions WHERE map = ? AND mode IN (?, ?)",
+        map,
+        mode,
+        all
+    )
+    .fetch_all(database)
+    .await?
+    .into_iter()
+    .map(|record| MapSubscription {
+        channel_id: record.channel_id as u64,
+        mentions: record.mentions,
+    })
+    .collect())
+}
+
+/// posts the new map to the subscribed channels whenever a rotation ends
+///
+/// the rotation is only requested again once the earliest map of all modes ends
+pub async fn post_map_changes(
+    ctx: serenity::Context,
+    database: sqlx::SqlitePool,
+    cache: Arc<ApexCache>,
+) {
+    // there is nothing to post without a token
+    if apex_client().is_err() {
+        return;
+    }
+    // the end of the current map of every mode, to know which rotation changed
+    let mut known: HashMap<MapMode, DateTime<Utc>> = HashMap::new();
+    // the modes whose rotation was requested before, nothing changed on their first request
+    let mut requested: HashSet<MapMode> = HashSet::new();
+    loop {
+        let mut failed = false;
+        for mode in MapMode::MODES {
+            let rotation = match cache.rotation(mode).await {
+                Ok(rotation) => rotation,
+                // the previous end is kept, a failed request isn't a new map
+                Err(why) => {
+                    tracing::warn!("couldn't request the {} rotation: {}", mode.name(), why);
+                    failed = true;
+                    continue;
+                }
+            };
+            match CurrentMap::of(&rotation) {
+                Some(map) => {
+                    if requested.contains(&mode)
+                        && known.get(&mode) != Some(&map.end)
+                        && map.end > Utc::now()
+                    {
+                        post_map_change(&ctx, &database, &map).await;
+                    }
+                    known.insert(mode, map.end);
+                }
+                None => {
+                    known.remove(&mode);
+                }
+            }
+            requested.insert(mode);
+        }
+
+        let now = Utc::now();
+        let retry = now + Duration::minutes(RETRY_DELAY_MINUTES);
+        let wake_up = match known.values().min() {
+            Some(next_end) if *next_end > now => *next_end,
+            // the api might not know the next map exactly at the end of the rotation
+            Some(_) => now + Duration::seconds(ROTATION_DELAY_SECONDS),
+            None => retry,
+        };
+        sleep_until(if failed { wake_up.min(retry) } else { wake_up }).await;
+    }
+}
+
+async fn post_map_change(ctx: &serenity::Context, database: &sqlx::SqlitePool, map: &CurrentMap) {
+    let subscriptions = match get_map_subscriptions(database, &map.name, map.mode).await {
+        Ok(subscriptions) => subscriptions,
+        Err(why) => {
+            tracing::error!("couldn't get map subscriptions: {}", why);
+            return;
+        }
+    };
+    for subscription in subscriptions {
+        if let Err(why) = serenity::ChannelId(subscription.channel_id)
+            .send_message(ctx, |m| {
+                if let Some(mentions) = &subscription.mentions {
+                    m.content(mentions);
+                }
+                m.set_embed(map.embed.clone())
+            })
+            .await
+        {
+            tracing::error!(
+                "couldn't post map change to <#{}>: {}",
+                subscription.channel_id,
+                why
+            );
+        }
+    }
+}
+
+async fn sleep_until(time: DateTime<Utc>) {
+    let duration = (time - Utc::now()).to_std().unwrap_or_default();
+    tokio::time::sleep(duration).await;
+}
+
+/// the platforms apex legends is played on
+#[derive(Clone, Copy, PartialEq, Debug, poise::ChoiceParameter)]
+pub enum Platform {
+    #[name = "pc"]
+    Pc,
+    #[name = "playstation"]
+    PlayStation,
+    #[name = "xbox"]
+    Xbox,
+    #[name = "switch"]
+    Switch,
+}
+
+impl Platform {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Pc => "pc",
+            Platform::PlayStation => "playstation",
+            Platform::Xbox => "xbox",
+            Platform::Switch => "switch",
+        }
+    }
+
+    /// how the platform is called by the apex api and stored in the database
+    pub fn key(&self) -> &'static str {
+        match self {
+            Platform::Pc => "PC",
+            Platform::PlayStation => "PS4",
+            Platform::Xbox => "X1",
+            Platform::Switch => "SWITCH",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Platform> {
+        [
+            Platform::Pc,
+            Platform::PlayStation,
+            Platform::Xbox,
+            Platform::Switch,
+        ]
+        .into_iter()
+        .find(|platform| platform.key() == key)
+    }
+}
+
+/// the stats of a player like the apex api sends them
+#[derive(Deserialize, Clone, Debug)]
+pub struct Player {
+    pub global: PlayerGlobal,
+    #[serde(default)]
+    pub realtime: Option<PlayerRealtime>,
+    #[serde(default)]
+    pub legends: Option<PlayerLegends>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PlayerGlobal {
+    pub name: String,
+    pub platform: String,
+    pub level: i64,
+    #[serde(rename = "toNextLevelPercent", default)]
+    pub to_next_level_percent: f64,
+    #[serde(default)]
+    pub rank: Option<PlayerRank>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PlayerRank {
+    #[serde(rename = "rankName")]
+    pub name: String,
+    #[serde(rename = "rankDiv", default)]
+    pub division: i64,
+    #[serde(rename = "rankScore", default)]
+    pub score: i64,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PlayerRealtime {
+    #[serde(rename = "selectedLegend", default)]
+    pub selected_legend: String,
+    #[serde(rename = "currentStateAsText", default)]
+    pub state: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PlayerLegends {
+    pub selected: SelectedLegend,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SelectedLegend {
+    #[serde(rename = "LegendName")]
+    pub name: String,
+    /// the trackers the player has equipped on the legend
+    #[serde(default)]
+    pub data: Vec<Tracker>,
+    #[serde(rename = "ImgAssets", default)]
+    pub assets: Option<LegendAssets>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Tracker {
+    pub name: String,
+    pub value: i64,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LegendAssets {
+    pub icon: Option<String>,
+}
+
+impl Player {
+    pub fn rank(&self) -> Option<String> {
+        self.global.rank.as_ref().map(|rank| match rank.division {
+            // the highest ranks have no divisions
+            0 => format!("{} ({} RP)", rank.name, rank.score),
+            division => format!("{} {} ({} RP)", rank.name, division, rank.score),
+        })
+    }
+
+    /// the legend the player currently plays, the api only knows the trackers of that one
+    pub fn legend(&self) -> Option<&SelectedLegend> {
+        self.legends.as_ref().map(|legends| &legends.selected)
+    }
+}
+
+/// the apex api answers with an error object instead of an error status
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PlayerResponse {
+    Error {
+        #[serde(rename = "Error")]
+        error: String,
+    },
+    Player(Player),
+}
+
+/// the endpoints of the apex api that apex-rs doesn't have, see `crate::apex_api`
+///
+/// apex-rs doesn't expose its http client, so these requests use a separate one.
+/// it's created once and shared by all of them instead of connecting again every time
+pub struct ApexApi {
+    http: reqwest::Client,
+    token: String,
+}
+
+impl ApexApi {
+    pub fn new(token: &str) -> Self {
+        ApexApi {
+            http: reqwest::Client::new(),
+            token: token.to_string(),
+        }
+    }
+
+    /// requests an endpoint of the apex api with the token of the config
+    async fn request<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
+        Ok(self
+            .http
+            .get(format!("{}/{}", API_URL, endpoint))
+            .query(&[("auth", self.token.as_str())])
+            .query(query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<T>()
+            .await?)
+    }
+}
+
+/// the apex account a discord user linked to themselves
+pub struct ApexAccount {
+    pub platform: Platform,
+    pub player: String,
+}
+
+pub async fn link_apex_account(
+    database: &sqlx::SqlitePool,
+    user_id: i64,
+    account: &ApexAccount,
+) -> Result<()> {
+    let platform = account.platform.key();
+    sqlx::query!(
+        "INSERT INTO apex_accounts (user_id, platform, player) VALUES (?, ?, ?)
+        ON CONFLICT(user_id) DO UPDATE SET platform = excluded.platform, player = excluded.player",
+        user_id,
+        platform,
+        account.player,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+pub async fn unlink_apex_account(database: &sqlx::SqlitePool, user_id: i64) -> Result<()> {
+    let result = sqlx::query!("DELETE FROM apex_accounts WHERE user_id = ?", user_id)
+        .execute(database)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(Box::new(AYError::InvalidInput(
+            "you didn't link an apex account",
+        )));
+    }
+    Ok(())
+}
+
+pub async fn get_apex_account(
+    database: &sqlx::SqlitePool,
+    user_id: i64,
+) -> Result<Option<ApexAccount>> {
+    let record = sqlx::query!(
+        "SELECT platform, player FROM apex_accounts WHERE user_id = ?",
+        user_id
+    )
+    .fetch_optional(database)
+    .await?;
+    Ok(record.and_then(|record| {
+        Platform::from_key(&record.platform).map(|platform| ApexAccount {
+            platform,
+            player: record.player,
+        })
+    }))
+}
+
+/// a token bucket in front of the apex api, requests wait for a token if one is free soon
+pub struct RateLimiter {
+    /// tokens per second
+    rate: f64,
+    capacity: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    /// negative if requests are waiting for their token
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64) -> Self {
+        // no rate would let every request wait forever
+        let rate = rate.max(0.1);
+        // a burst can't be bigger than a second of requests
+        let capacity = rate.max(1.0);
+        RateLimiter {
+            rate,
+            capacity,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                updated_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// takes a token, fails with the time until one is free if that's too long
+    pub async fn acquire(&self) -> Result<()> {
+        let wait = {
+            let mut bucket = self.bucket.lock().await;
+            let now = Instant::now();
+            let refilled = (now - bucket.updated_at).as_secs_f64() * self.rate;
+            bucket.tokens = (bucket.tokens + refilled).min(self.capacity);
+            bucket.updated_at = now;
+            let wait = StdDuration::from_secs_f64(((1.0 - bucket.tokens) / self.rate).max(0.0));
+            if wait > MAX_RATE_LIMIT_WAIT {
+                return Err(Box::new(AYError::Unavailable(
+                    "the apex api is busy",
+                    Some(wait),
+                )));
+            }
+            // the token is reserved, even if it's refilled while waiting
+            bucket.tokens -= 1.0;
+            wait
+        };
+        tokio::time::sleep(wait).await;
+        Ok(())
+    }
+}
+
+/// a response of the apex api and until when it's used
+struct CachedResponse {
+    value: Arc<dyn Any + Send + Sync>,
+    expires_at: DateTime<Utc>,
+}
+
+/// the current and next map of a mode
+pub struct ModeRotation {
+    pub mode: MapMode,
+    pub current: Option<Map>,
+    pub next: Option<Map>,
+}
+
+/// process-wide cache of the apex api responses by endpoint, to stay below the rate limit
+pub struct ApexCache {
+    /// how long responses other than the map rotation are used
+    ttl: Duration,
+    limiter: RateLimiter,
+    responses: RwLock<HashMap<String, CachedResponse>>,
+    /// one lock per endpoint, so concurrent requests of an endpoint share one api request
+    requests: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl ApexCache {
+    pub fn new(ttl_seconds: i64, rate_limit: f64) -> Self {
+        ApexCache {
+            ttl: Duration::seconds(ttl_seconds),
+            limiter: RateLimiter::new(rate_limit),
+            responses: RwLock::new(HashMap::new()),
+            requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// the cached response of `endpoint`, the request answers with the response and its expiry
+    ///
+    /// with `stale` the expired response is used if the api can't be reached or is busy,
+    /// for a while after it expired
+    async fn cached<T, F>(&self, endpoint: &str, stale: bool, request: F) -> Result<Arc<T>>
+    where
+        T: Send + Sync + 'static,
+        F: Future<Output = Result<(T, DateTime<Utc>)>>,
+    {
+        if let Some((value, expires_at)) = self.lookup::<T>(endpoint).await {
+            if Utc::now() < expires_at {
+                return Ok(value);
+            }
+        }
+
+        let lock = self
+            .requests
+            .lock()
+            .await
+            .entry(endpoint.to_string())
+            .or_default()
+            .clone();
+        let response = {
+            let _request = lock.lock().await;
+            self.request(endpoint, stale, request).await
+        };
+        // nobody else waits for the endpoint if only the map and this request hold the lock
+        let mut requests = self.requests.lock().await;
+        if Arc::strong_count(&lock) == 2 {
+            requests.remove(endpoint);
+        }
+        response
+    }
+
+    async fn lookup<T>(&self, endpoint: &str) -> Option<(Arc<T>, DateTime<Utc>)>
+    where
+        T: Send + Sync + 'static,
+    {
+        self.responses
+            .read()
+            .await
+            .get(endpoint)
+            .and_then(|response| {
+                Some((
+                    response.value.clone().downcast::<T>().ok()?,
+                    response.expires_at,
+                ))
+            })
+    }
+
+    /// requests the endpoint while holding its lock
+    async fn request<T, F>(&self, endpoint: &str, stale: bool, request: F) -> Result<Arc<T>>
+    where
+        T: Send + Sync + 'static,
+        F: Future<Output = Result<(T, DateTime<Utc>)>>,
+    {
+        // the request that held the lock before might have answered already
+        let cached = self.lookup::<T>(endpoint).await;
+        if let Some((value, expires_at)) = &cached {
+            if Utc::now() < *expires_at {
+                return Ok(value.clone());
+            }
+        }
+
+        let response = match self.limiter.acquire().await {
+            Ok(()) => request.await,
+            Err(why) => Err(why),
+        };
+        match response {
+            Ok((value, expires_at)) => {
+                let value = Arc::new(value);
+                let mut responses = self.responses.write().await;
+                // players that are looked up once would stay forever
+                let now = Utc::now();
+                responses.retain(|_, response| {
+                    now - response.expires_at < Duration::minutes(MAX_STALE_MINUTES)
+                });
+                responses.insert(
+                    endpoint.to_string(),
+                    CachedResponse {
+                        value: value.clone(),
+                        expires_at,
+                    },
+                );
+                Ok(value)
+            }
+            Err(why) => match cached.filter(|(_, expires_at)| {
+                stale && Utc::now() - *expires_at < Duration::minutes(MAX_STALE_MINUTES)
+            }) {
+                Some((value, _)) => {
+                    tracing::warn!("couldn't request apex endpoint {}: {}", endpoint, why);
+                    Ok(value)
+                }
+                None => Err(why),
+            },
+        }
+    }
+
+    /// the response of an endpoint that is used for the configured ttl
+    async fn cached_api<T>(&self, endpoint: &str, query: &[(&str, &str)]) -> Result<Arc<T>>
+    where
+        T: DeserializeOwned + Send + Sync + 'static,
+    {
+        let key = query.iter().fold(endpoint.to_string(), |key, (_, value)| {
+            format!("{}/{}", key, value.to_lowercase())
+        });
+        self.cached(&key, true, async {
+            let response = apex_api()?.request(endpoint, query).await?;
+            Ok((response, Utc::now() + self.ttl))
+        })
+        .await
+    }
+
+    /// the rotation of the mode, it's used until the current map ends
+    ///
+    /// an expired rotation is never used, its map has ended.
+    /// all modes is the battle royale rotation and shares its entry
+    pub async fn rotation(&self, mode: MapMode) -> Result<Arc<ModeRotation>> {
+        let mode = match mode {
+            MapMode::All => MapMode::BattleRoyal,
+            mode => mode,
+        };
+        let endpoint = format!("maprotation/{}", mode.key());
+        self.cached(&endpoint, false, async {
+            let client = apex_client()?;
+            let rotation = match mode {
+                MapMode::BattleRoyal | MapMode::All => client.battle_royal_rotation().await?,
+                MapMode::Ranked => client.ranked_rotation().await?,
+                MapMode::Arenas => client.arenas_rotation().await?,
+                MapMode::Ltm => client.ltm_rotation().await?,
+            };
+            let rotation = ModeRotation {
+                mode,
+                current: rotation.current().cloned(),
+                next: rotation.next().cloned(),
+            };
+            // modes without a map, like a limited time mode that isn't running, use the ttl
+            let expires_at = rotation
+                .current
+                .as_ref()
+                .map_or(Utc::now() + self.ttl, |map| map.end_as_date());
+            Ok((rotation, expires_at))
+        })
+        .await
+    }
+
+    /// the stats of the player
+    pub async fn player(&self, platform: Platform, player: &str) -> Result<Player> {
+        let response = self
+            .cached_api::<PlayerResponse>(
+                "bridge",
+                &[("player", player.trim()), ("platform", platform.key())],
+            )
+            .await?;
+        match response.as_ref() {
+            PlayerResponse::Player(player) => Ok(player.clone()),
+            PlayerResponse::Error { error } => {
+                tracing::debug!("couldn't get apex player {}: {}", player, error);
+                Err(Box::new(AYError::InvalidInput(
+                    "couldn't find that player on the platform",
+                )))
+            }
+        }
+    }
+
+    pub async fn crafting(&self) -> Result<Arc<Vec<CraftingBundle>>> {
+        self.cached_api("crafting", &[]).await
+    }
+
+    pub async fn predator(&self) -> Result<Arc<PredatorThresholds>> {
+        self.cached_api("predator", &[]).await
+    }
+
+    pub async fn server_status(&self) -> Result<Arc<ServerStatus>> {
+        self.cached_api("servers", &[]).await
+    }
+}
+
+/// the items the replicator can craft for some time
+#[derive(Deserialize, Clone, Debug)]
+pub struct CraftingBundle {
+    /// daily, weekly or permanent
+    #[serde(rename = "bundleType")]
+    pub kind: String,
+    /// unix timestamp of the end, permanent bundles don't end
+    #[serde(default)]
+    pub end: Option<i64>,
+    #[serde(rename = "bundleContent", default)]
+    pub content: Vec<CraftingItem>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CraftingItem {
+    pub cost: i64,
+    #[serde(rename = "itemType")]
+    pub item_type: CraftingItemType,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CraftingItemType {
+    pub name: String,
+    #[serde(default)]
+    pub rarity: String,
+}
+
+/// the points needed for predator by platform, like PC or PS4
+#[derive(Deserialize, Clone, Debug)]
+pub struct PredatorThresholds {
+    #[serde(rename = "RP")]
+    pub battle_royal: BTreeMap<String, PredatorThreshold>,
+    #[serde(rename = "AP", default)]
+    pub arenas: BTreeMap<String, PredatorThreshold>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PredatorThreshold {
+    #[serde(rename = "val")]
+    pub value: i64,
+    #[serde(rename = "totalMastersAndPreds", default)]
+    pub masters_and_predators: i64,
+}
+
+/// the status of every region by service, like EA_accounts
+pub type ServerStatus = BTreeMap<String, BTreeMap<String, RegionStatus>>;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RegionStatus {
+    /// UP, SLOW or DOWN
+    #[serde(rename = "Status")]
+    pub status: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn cache() -> ApexCache {
+        ApexCache::new(DEFAULT_TTL_SECONDS, 100.0)
+    }
+
+    async fn answer(value: u8, expires_at: DateTime<Utc>) -> Result<(u8, DateTime<Utc>)> {
+        Ok((value, expires_at))
+    }
+
+    async fn fail() -> Result<(u8, DateTime<Utc>)> {
+        Err(Box::new(AYError::Unavailable("the apex api is busy", None)))
+    }
+
+    fn player_response(json: &str) -> PlayerResponse {
+        serde_json::from_str(json).expect("fixture response")
+    }
+
+    #[test]
+    fn a_recorded_player_is_decoded() {
+        let player = match player_response(include_str!("../fixtures/apex/bridge.json")) {
+            PlayerResponse::Player(player) => player,
+            PlayerResponse::Error { error } => panic!("decoded as error: {}", error),
+        };
+        assert_eq!(player.global.name, "Nirusu");
+        assert_eq!(player.global.level, 312);
+        assert_eq!(player.rank().as_deref(), Some("Platinum 2 (7420 RP)"));
+        let legend = player.legend().unwrap();
+        assert_eq!(legend.name, "Wraith");
+        assert_eq!(
+            legend
+                .data
+                .iter()
+                .map(|tracker| tracker.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["BR Kills", "BR Wins", "BR Damage"]
+        );
+        assert_eq!(player.realtime.unwrap().state, "In lobby (00:42)");
+    }
+
+    #[test]
+    fn an_error_response_is_no_player() {
+        match player_response(include_str!("../fixtures/apex/bridge_error.json")) {
+            PlayerResponse::Error { error } => assert_eq!(error, "Player Nobody not found"),
+            PlayerResponse::Player(_) => panic!("decoded the error as a player"),
+        }
+    }
+
+    #[test]
+    fn recorded_crafting_bundles_are_decoded() {
+        let bundles: Vec<CraftingBundle> =
+            serde_json::from_str(include_str!("../fixtures/apex/crafting.json")).unwrap();
+        assert_eq!(
+            bundles
+                .iter()
+                .map(|bundle| bundle.kind.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["daily", "weekly", "permanent"]
+        );
+        assert_eq!(bundles[0].end, Some(1686157200));
+        assert_eq!(bundles[0].content[0].cost, 30);
+        assert_eq!(bundles[0].content[1].item_type.rarity, "Rare");
+        assert!(bundles[1].content.is_empty());
+        // permanent bundles don't end
+        assert_eq!(bundles[2].end, None);
+    }
+
+    #[test]
+    fn recorded_predator_thresholds_are_decoded() {
+        let thresholds: PredatorThresholds =
+            serde_json::from_str(include_str!("../fixtures/apex/predator.json")).unwrap();
+        assert_eq!(
+            thresholds.battle_royal.keys().collect::<Vec<&String>>(),
+            vec!["PC", "PS4", "SWITCH", "X1"]
+        );
+        let pc = &thresholds.battle_royal["PC"];
+        assert_eq!((pc.value, pc.masters_and_predators), (17642, 12418));
+        assert_eq!(thresholds.arenas["PC"].value, 9120);
+    }
+
+    #[test]
+    fn recorded_server_status_is_decoded() {
+        let status: ServerStatus =
+            serde_json::from_str(include_str!("../fixtures/apex/servers.json")).unwrap();
+        assert_eq!(
+            status.keys().collect::<Vec<&String>>(),
+            vec!["EA_accounts", "Origin_login", "otherPlatforms"]
+        );
+        assert_eq!(status["EA_accounts"]["Asia"].status, "DOWN");
+        assert_eq!(status["Origin_login"]["US-East"].status, "SLOW");
+        assert_eq!(status["otherPlatforms"]["Xbox-Live"].status, "UP");
+    }
+
+    #[test]
+    fn map_names_are_compared_without_case_spaces_or_apostrophes() {
+        assert_eq!(map_key("World's Edge"), "worldsedge");
+        assert_eq!(map_key(" worlds edge "), map_key("World's Edge"));
+        assert_eq!(map_key("Habitat 4"), "habitat4");
+        assert!(map_key(" ' ").is_empty());
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_of_an_endpoint_share_one_request() {
+        let cache = cache();
+        let requests = &AtomicUsize::new(0);
+        let request = move || async move {
+            requests.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(StdDuration::from_millis(50)).await;
+            answer(1, Utc::now() + Duration::minutes(1)).await
+        };
+        let (first, second) = tokio::join!(
+            cache.cached("servers", true, request()),
+            cache.cached("servers", true, request())
+        );
+        assert_eq!(*first.unwrap(), 1);
+        assert_eq!(*second.unwrap(), 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert!(cache.requests.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_responses_are_only_used_for_stale_endpoints() {
+        let cache = cache();
+        let expired = Utc::now() - Duration::seconds(1);
+        cache
+            .cached("servers", true, answer(1, expired))
+            .await
+            .unwrap();
+        cache
+            .cached("maprotation/ltm", false, answer(2, expired))
+            .await
+            .unwrap();
+
+        assert_eq!(*cache.cached("servers", true, fail()).await.unwrap(), 1);
+        assert!(cache
+            .cached("maprotation/ltm", false, fail())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn long_expired_responses_are_dropped() {
+        let cache = cache();
+        let long_ago = Utc::now() - Duration::minutes(MAX_STALE_MINUTES + 1);
+        cache
+            .cached("bridge/old/pc", true, answer(1, long_ago))
+            .await
+            .unwrap();
+        cache
+            .cached(
+                "bridge/new/pc",
+                true,
+                answer(2, Utc::now() + Duration::minutes(1)),
+            )
+            .await
+            .unwrap();
+
+        let responses = cache.responses.read().await;
+        assert!(!responses.contains_key("bridge/old/pc"));
+        assert!(responses.contains_key("bridge/new/pc"));
+        drop(responses);
+        assert!(cache.cached("bridge/old/pc", true, fail()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn busy_errors_keep_their_retry_after() {
+        // a rate of 0.1 has a single token and waits 10 seconds for the next one
+        let cache = ApexCache::new(DEFAULT_TTL_SECONDS, 0.1);
+        let expired = Utc::now() - Duration::seconds(1);
+        cache
+            .cached("maprotation/ranked", false, answer(1, expired))
+            .await
+            .unwrap();
+        let why = cache
+            .cached("maprotation/ranked", false, answer(2, expired))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            why.downcast_ref::<AYError>(),
+            Some(AYError::Unavailable(_, Some(_)))
+        ));
+    }
+}
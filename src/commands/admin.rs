@@ -1,22 +1,84 @@
+use std::{borrow::Cow, collections::HashSet, sync::Arc};
+
 use crate::{
-    util::{add_user_exclusion, get_user_exclusions, remove_user_exclusion, Exclusion},
+    error::Error as AYError,
+    menu::{self, Cursor, Menu},
+    util::{
+        add_exclusion_audit, add_exclusion_group_member, add_group_audit, add_user_exclusion,
+        apply_exclusion_changes, compute_exclusion_changes, create_exclusion_group,
+        delete_exclusion_group, from_timestamp, get_exclusion_audit, get_exclusion_group_id,
+        get_exclusion_groups, get_moderator_role, get_user_exclusion_by_id,
+        get_user_exclusion_by_users, get_user_exclusions, import_user_exclusions,
+        lift_user_exclusion, release_text_channels, remove_exclusion_group_member,
+        set_exclusion_channel, set_moderator_role, set_simulate_channel, set_text_policy,
+        sync_group_exclusions, sync_guild_exclusions, to_relative_timestamp, AuditEntry, Exclusion,
+        ExclusionChange, ExclusionDirection, GroupMember, TextPolicy,
+    },
     Context, Result,
 };
 use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+
+const HISTORY_PAGE_SIZE: usize = 10;
+const SYNC_PREVIEW_SIZE: usize = 25;
+const IMPORT_SKIPPED_SIZE: usize = 10;
 
 /// overview about all exclusions on this server
 #[poise::command(
     track_edits,
     slash_command,
-    subcommands("add", "remove", "list"),
+    subcommands(
+        "add",
+        "remove",
+        "list",
+        "history",
+        "moderator",
+        "sync",
+        "group",
+        "policy",
+        "optin",
+        "optout",
+        "simulate",
+        "export",
+        "import"
+    ),
     guild_only
 )]
 pub async fn exclusions(ctx: Context<'_>) -> Result<()> {
     Ok(())
 }
 
+/// checks if the author has the manage channels permission or the moderator role of the guild
+async fn is_exclusion_moderator(ctx: Context<'_>) -> Result<bool> {
+    let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
+    let member = ctx
+        .author_member()
+        .await
+        .ok_or_else(|| AYError::InvalidInput("not in a guild"))?;
+    if member
+        .permissions(ctx.serenity_context())
+        .map_or(false, |permissions| {
+            permissions.contains(serenity::Permissions::MANAGE_CHANNELS)
+        })
+    {
+        return Ok(true);
+    }
+    let role = get_moderator_role(&ctx.data().database, guild_id as i64).await?;
+    if role.map_or(false, |role| member.roles.contains(&serenity::RoleId(role))) {
+        return Ok(true);
+    }
+    Err(Box::new(AYError::InvalidInput(
+        "you need the manage channels permission or the moderator role",
+    )))
+}
+
 /// list all exclusions on this server
-#[poise::command(track_edits, slash_command, guild_only)]
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    check = "is_exclusion_moderator"
+)]
 pub async fn list(ctx: Context<'_>) -> Result<()> {
     let exclusions = get_user_exclusions(
         &ctx.data().database,
@@ -37,14 +99,29 @@ pub fn embed_exclusions(
         .color(crate::color())
         .description(
             "People in the exclusion list can't see each other.
+            With a one way exclusion only the ignoring user loses sight of the other one.
             You can add user that shouldn't see each other with `/exclusions add <user> <user>`
             and remove them with `/exclusions remove <index>`",
         );
-    for (index, exclusion) in exclusions.iter().enumerate() {
+    for exclusion in exclusions.iter() {
         let (id_1, id_2) = exclusion.users();
+        let mut text = match exclusion.direction() {
+            ExclusionDirection::Mutual => format!("<@{}> and <@{}> ignore each other", id_1, id_2),
+            ExclusionDirection::OneWay(user) => format!(
+                "<@{}> ignores <@{}>",
+                user,
+                exclusion.partner(user).unwrap_or_default()
+            ),
+        };
+        if let Some(reason) = exclusion.reason() {
+            text.push_str(&format!("\nReason: {}", reason));
+        }
+        if let Some(expires_at) = exclusion.expires_at() {
+            text.push_str(&format!("\nExpires {}", to_relative_timestamp(expires_at)));
+        }
         embed.field(
-            format!("{} Exclusion", index),
-            format!("<@{}> ignores <@{}>", id_1, id_2),
+            format!("{} Exclusion", exclusion.id().unwrap_or_default()),
+            text,
             false,
         );
     }
@@ -52,29 +129,974 @@ pub fn embed_exclusions(
 }
 
 /// add two user that should ignore each other
-#[poise::command(track_edits, slash_command, guild_only)]
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    check = "is_exclusion_moderator"
+)]
 pub async fn add(
     ctx: Context<'_>,
     #[description = "first user that ignores the second"] user_1: serenity::User,
-    #[description = "second user that ignores the first"] user_2: serenity::User,
+    #[description = "second user, ignores the first unless it's one way"] user_2: serenity::User,
+    #[description = "why the users should ignore each other"] reason: Option<String>,
+    #[description = "hours until the exclusion is lifted"] hours: Option<u32>,
+    #[description = "only the first user ignores the second"] one_way: Option<bool>,
 ) -> Result<()> {
     let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
-    let exclusion = Exclusion::from((user_1.id.0, user_2.id.0));
+    let expires_at = hours.map(|hours| chrono::Utc::now() + chrono::Duration::hours(hours.into()));
+    let exclusion = Exclusion::from((user_1.id.0, user_2.id.0))
+        .with_reason(reason.clone())
+        .with_expiry(expires_at)
+        .with_direction(if one_way.unwrap_or(false) {
+            ExclusionDirection::OneWay(user_1.id.0)
+        } else {
+            ExclusionDirection::Mutual
+        });
     add_user_exclusion(&ctx.data().database, guild_id as i64, &exclusion).await?;
+    ctx.data()
+        .text_policies
+        .invalidate(serenity::GuildId(guild_id))
+        .await;
+    add_exclusion_audit(
+        &ctx.data().database,
+        guild_id as i64,
+        ctx.author().id.0,
+        "add",
+        &exclusion,
+        reason.as_deref(),
+    )
+    .await?;
     ctx.say("Added to the exclusion list").await?;
     Ok(())
 }
 
-/// remove a user exclusion
-#[poise::command(track_edits, slash_command, guild_only)]
+/// remove a user exclusion by its index or by both users
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    check = "is_exclusion_moderator"
+)]
 pub async fn remove(
     ctx: Context<'_>,
-    #[description = "first user that ignores the second"] user_1: serenity::User,
-    #[description = "second user that ignores the first"] user_2: serenity::User,
+    #[description = "index of the exclusion"] index: Option<i64>,
+    #[description = "first user of the exclusion"] user_1: Option<serenity::User>,
+    #[description = "second user of the exclusion"] user_2: Option<serenity::User>,
+    #[description = "why the exclusion is removed"] reason: Option<String>,
 ) -> Result<()> {
     let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
-    let exclusion = Exclusion::from((user_1.id.0, user_2.id.0));
-    remove_user_exclusion(&ctx.data().database, guild_id as i64, &exclusion).await?;
+    let database = &ctx.data().database;
+    let exclusion = match (index, user_1, user_2) {
+        (Some(index), _, _) => get_user_exclusion_by_id(database, guild_id as i64, index).await?,
+        (None, Some(user_1), Some(user_2)) => {
+            get_user_exclusion_by_users(database, guild_id as i64, (user_1.id.0, user_2.id.0))
+                .await?
+        }
+        _ => {
+            return Err(Box::new(AYError::InvalidInput(
+                "either an index or two users are needed",
+            )))
+        }
+    }
+    .ok_or_else(|| AYError::InvalidInput("exclusion doesn't exist"))?;
+    lift_user_exclusion(
+        ctx.serenity_context(),
+        database,
+        guild_id as i64,
+        &exclusion,
+        ctx.author().id.0,
+        "remove",
+        reason.as_deref(),
+    )
+    .await?;
+    ctx.data()
+        .text_policies
+        .invalidate(serenity::GuildId(guild_id))
+        .await;
     ctx.say("Removed from the exclusion list").await?;
     Ok(())
 }
+
+/// set the role that is allowed to manage exclusions
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn moderator(
+    ctx: Context<'_>,
+    #[description = "role that can manage exclusions, none to reset"] role: Option<serenity::Role>,
+) -> Result<()> {
+    let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
+    set_moderator_role(
+        &ctx.data().database,
+        guild_id as i64,
+        role.as_ref().map(|role| role.id.0),
+    )
+    .await?;
+    let message = match role {
+        Some(role) => format!("<@&{}> can now manage exclusions", role.id.0),
+        None => {
+            String::from("Only members with the manage channels permission can manage exclusions")
+        }
+    };
+    ctx.say(message).await?;
+    Ok(())
+}
+
+/// set how exclusions are applied to text channels
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    check = "is_exclusion_moderator"
+)]
+pub async fn policy(
+    ctx: Context<'_>,
+    #[description = "ignore text channels, use recent messages or opted in channels"]
+    policy: TextPolicy,
+    #[description = "minutes a message counts as activity (activity policy)"] minutes: Option<u32>,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default();
+    let data = ctx.data();
+    // the configured window is kept if none is given
+    let configured = data
+        .text_policies
+        .get(&data.database, guild_id)
+        .await?
+        .activity_minutes;
+    let minutes = minutes.map_or(configured, i64::from);
+    set_text_policy(&data.database, guild_id.into(), policy, minutes).await?;
+    data.text_policies.invalidate(guild_id).await;
+    // overwrites of text channels the policy doesn't use anymore are revoked
+    release_text_channels(
+        ctx.serenity_context(),
+        &data.database,
+        &data.text_policies,
+        guild_id,
+    )
+    .await?;
+    let message = match policy {
+        TextPolicy::Ignore => String::from("Text channels are ignored by the exclusions"),
+        TextPolicy::Activity => format!(
+            "Users that wrote in a text channel in the last {} minutes can't be seen by excluded users",
+            minutes
+        ),
+        TextPolicy::OptIn => String::from(
+            "Only text channels added with `/exclusions optin <channel>` are used by the exclusions",
+        ),
+    };
+    ctx.say(message).await?;
+    Ok(())
+}
+
+/// use a text channel for exclusions (opt-in policy)
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    check = "is_exclusion_moderator"
+)]
+pub async fn optin(
+    ctx: Context<'_>,
+    #[description = "text channel that should be used"] channel: serenity::GuildChannel,
+) -> Result<()> {
+    let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
+    set_exclusion_channel(&ctx.data().database, guild_id as i64, channel.id.0, true).await?;
+    ctx.say(format!("<#{}> is now used by the exclusions", channel.id.0))
+        .await?;
+    Ok(())
+}
+
+/// stop using a text channel for exclusions (opt-in policy)
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    check = "is_exclusion_moderator"
+)]
+pub async fn optout(
+    ctx: Context<'_>,
+    #[description = "text channel that shouldn't be used anymore"] channel: serenity::GuildChannel,
+) -> Result<()> {
+    let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
+    set_exclusion_channel(&ctx.data().database, guild_id as i64, channel.id.0, false).await?;
+    release_text_channels(
+        ctx.serenity_context(),
+        &ctx.data().database,
+        &ctx.data().text_policies,
+        serenity::GuildId(guild_id),
+    )
+    .await?;
+    ctx.say(format!(
+        "<#{}> is not used by the exclusions anymore",
+        channel.id.0
+    ))
+    .await?;
+    Ok(())
+}
+
+/// log the overwrites of the exclusions to a channel instead of applying them
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn simulate(
+    ctx: Context<'_>,
+    #[description = "channel for the simulated overwrites, none to apply them again"]
+    channel: Option<serenity::GuildChannel>,
+) -> Result<()> {
+    let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
+    set_simulate_channel(
+        &ctx.data().database,
+        guild_id as i64,
+        channel.as_ref().map(|channel| channel.id.0),
+    )
+    .await?;
+    let message = match channel {
+        Some(channel) => format!(
+            "Exclusion overwrites are now logged to <#{}> instead of being applied",
+            channel.id.0
+        ),
+        None => String::from("Exclusion overwrites are applied again"),
+    };
+    ctx.say(message).await?;
+    Ok(())
+}
+
+/// show who added or removed exclusions on this server
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    check = "is_exclusion_moderator"
+)]
+pub async fn history(ctx: Context<'_>) -> Result<()> {
+    let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
+    let entries = get_exclusion_audit(&ctx.data().database, guild_id as i64).await?;
+    if entries.is_empty() {
+        ctx.say("No exclusion changes on this server").await?;
+        return Ok(());
+    }
+    let pages = entries
+        .chunks(HISTORY_PAGE_SIZE)
+        .map(|page| page.iter().collect())
+        .collect::<Vec<Vec<&AuditEntry>>>();
+
+    let prev_action = menu::Control::new(
+        menu::MenuComponent::button("prev", |button| {
+            button.style(serenity::ButtonStyle::Primary).label("<")
+        }),
+        Arc::new(|menu, mci| Box::pin(select_prev_page(menu, mci))),
+    );
+    let next_action = menu::Control::new(
+        menu::MenuComponent::button("next", |button| {
+            button.style(serenity::ButtonStyle::Primary).label(">")
+        }),
+        Arc::new(|menu, mci| Box::pin(select_next_page(menu, mci))),
+    );
+
+    let mut menu = Menu::new(&ctx, Cursor::from(&pages), |options| {
+        options.add_row(|row| row.add_button(prev_action).add_button(next_action))
+    });
+    let embed = embed_exclusion_history(&menu.data);
+    menu.run(|m| {
+        m.embed(|e| {
+            e.clone_from(&embed);
+            e
+        })
+    })
+    .await
+}
+
+/// the function to show the previous page of the history
+async fn select_prev_page(
+    m: &mut Menu<'_, Cursor<'_, Vec<&AuditEntry>>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<()> {
+    m.data.prev();
+    let embed = embed_exclusion_history(&m.data);
+    m.update_response(|m| m.set_embed(embed), mci).await?;
+    Ok(())
+}
+
+/// the function to show the next page of the history
+async fn select_next_page(
+    m: &mut Menu<'_, Cursor<'_, Vec<&AuditEntry>>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<()> {
+    m.data.next();
+    let embed = embed_exclusion_history(&m.data);
+    m.update_response(|m| m.set_embed(embed), mci).await?;
+    Ok(())
+}
+
+pub fn embed_exclusion_history(cursor: &Cursor<'_, Vec<&AuditEntry>>) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    embed
+        .title("Exclusion history")
+        .color(crate::color())
+        .footer(|f| f.text(format!("page {}/{}", cursor.index() + 1, cursor.len())));
+    for entry in cursor.current().into_iter().flatten() {
+        let (id_1, id_2) = entry.users;
+        let value = match &entry.group {
+            Some((name, member)) => format!(
+                "{}`{}` by <@{}> {}",
+                member.map_or(String::new(), |member| format!("{} in ", member.mention())),
+                name,
+                entry.actor,
+                to_relative_timestamp(&entry.created_at),
+            ),
+            None => format!(
+                "<@{}> and <@{}> by <@{}> {}\nReason: {}",
+                id_1,
+                id_2,
+                entry.actor,
+                to_relative_timestamp(&entry.created_at),
+                entry.reason.as_deref().unwrap_or("None"),
+            ),
+        };
+        embed.field(format!("#{} {}", entry.id, entry.action), value, false);
+    }
+    embed
+}
+
+/// compare all channels with the exclusions and fix the differences
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    check = "is_exclusion_moderator"
+)]
+pub async fn sync(ctx: Context<'_>) -> Result<()> {
+    ctx.defer().await?;
+    let guild_id = ctx.guild_id().unwrap_or_default();
+    let changes =
+        compute_exclusion_changes(ctx.serenity_context(), ctx.data(), guild_id, None).await?;
+    if changes.is_empty() {
+        ctx.say("All channels are in sync with the exclusions")
+            .await?;
+        return Ok(());
+    }
+
+    let confirm_action = menu::Control::new(
+        menu::MenuComponent::button("confirm", |button| {
+            button.style(serenity::ButtonStyle::Success).label("apply")
+        }),
+        Arc::new(|menu, mci| Box::pin(confirm_sync(menu, mci))),
+    );
+    let cancel_action = menu::Control::new(
+        menu::MenuComponent::button("cancel", |button| {
+            button.style(serenity::ButtonStyle::Danger).label("cancel")
+        }),
+        Arc::new(|menu, mci| Box::pin(cancel_sync(menu, mci))),
+    );
+
+    let embed = embed_exclusion_changes(&changes);
+    let mut menu = Menu::new(&ctx, changes, |options| {
+        options.add_row(|row| row.add_button(confirm_action).add_button(cancel_action))
+    });
+    menu.run(|m| {
+        m.embed(|e| {
+            e.clone_from(&embed);
+            e
+        })
+    })
+    .await
+}
+
+/// checks if the button was pressed by the moderator that invoked the command,
+/// everyone else gets an ephemeral refusal
+async fn is_menu_author<T>(
+    m: &Menu<'_, T>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<bool> {
+    if mci.user.id == m.ctx.author().id {
+        return Ok(true);
+    }
+    mci.create_interaction_response(m.ctx.serenity_context(), |ir| {
+        ir.kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+            .interaction_response_data(|d| {
+                d.content("Only the moderator that invoked the command can use these buttons")
+                    .ephemeral(true)
+            })
+    })
+    .await?;
+    Ok(false)
+}
+
+/// the function to apply the pending changes of the sync
+async fn confirm_sync(
+    m: &mut Menu<'_, Vec<ExclusionChange>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<()> {
+    if !is_menu_author(m, mci).await? {
+        return Ok(());
+    }
+    apply_exclusion_changes(
+        m.ctx.serenity_context(),
+        m.ctx.data(),
+        m.ctx.guild_id().unwrap_or_default(),
+        &m.data,
+    )
+    .await?;
+    m.ctx
+        .say(format!("Applied {} changes", m.data.len()))
+        .await?;
+    m.stop();
+    Ok(())
+}
+
+/// the function to discard the pending changes of the sync
+async fn cancel_sync(
+    m: &mut Menu<'_, Vec<ExclusionChange>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<()> {
+    if !is_menu_author(m, mci).await? {
+        return Ok(());
+    }
+    m.stop();
+    Ok(())
+}
+
+pub fn embed_exclusion_changes(changes: &[ExclusionChange]) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    let mut description = changes
+        .iter()
+        .take(SYNC_PREVIEW_SIZE)
+        .map(|change| change.describe())
+        .collect::<Vec<String>>()
+        .join("\n");
+    if changes.len() > SYNC_PREVIEW_SIZE {
+        description.push_str(&format!(
+            "\n... and {} more",
+            changes.len() - SYNC_PREVIEW_SIZE
+        ));
+    }
+    embed
+        .title(format!("{} pending exclusion changes", changes.len()))
+        .description(description)
+        .color(crate::color());
+    embed
+}
+
+/// groups of users and roles that can't see each other
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    subcommands(
+        "group_create",
+        "group_add",
+        "group_remove",
+        "group_list",
+        "group_delete"
+    )
+)]
+pub async fn group(_ctx: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+async fn autocomplete_group<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
+    get_exclusion_groups(&ctx.data().database, guild_id as i64)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|group| group.name)
+        .filter(move |name| name.starts_with(partial))
+}
+
+/// the user or role of the command arguments as group member
+fn group_member(user: Option<serenity::User>, role: Option<serenity::Role>) -> Result<GroupMember> {
+    match (user, role) {
+        (Some(user), None) => Ok(GroupMember::User(user.id.0)),
+        (None, Some(role)) => Ok(GroupMember::Role(role.id.0)),
+        _ => Err(Box::new(AYError::InvalidInput(
+            "either a user or a role is needed",
+        ))),
+    }
+}
+
+/// create a new exclusion group
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    rename = "create",
+    check = "is_exclusion_moderator"
+)]
+pub async fn group_create(
+    ctx: Context<'_>,
+    #[description = "name of the group"] name: String,
+) -> Result<()> {
+    let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
+    let database = &ctx.data().database;
+    create_exclusion_group(database, guild_id as i64, &name).await?;
+    add_group_audit(
+        database,
+        guild_id as i64,
+        ctx.author().id.0,
+        "group create",
+        &name,
+        None,
+    )
+    .await?;
+    ctx.say(format!("Created the group `{}`", name)).await?;
+    Ok(())
+}
+
+/// add a user or a role to an exclusion group
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    rename = "add",
+    check = "is_exclusion_moderator"
+)]
+pub async fn group_add(
+    ctx: Context<'_>,
+    #[description = "name of the group"]
+    #[autocomplete = "autocomplete_group"]
+    name: String,
+    #[description = "user that can't see the other members"] user: Option<serenity::User>,
+    #[description = "role that can't see the other members"] role: Option<serenity::Role>,
+) -> Result<()> {
+    ctx.defer().await?;
+    let guild_id = ctx.guild_id().unwrap_or_default();
+    let database = &ctx.data().database;
+    let member = group_member(user, role)?;
+    let group_id = get_exclusion_group_id(database, guild_id.into(), &name).await?;
+    add_exclusion_group_member(database, group_id, member).await?;
+    add_group_audit(
+        database,
+        guild_id.into(),
+        ctx.author().id.0,
+        "group add",
+        &name,
+        Some(member),
+    )
+    .await?;
+    ctx.data().text_policies.invalidate(guild_id).await;
+    sync_group_exclusions(ctx.serenity_context(), ctx.data(), guild_id, group_id).await?;
+    ctx.say(format!(
+        "Added {} to the group `{}`",
+        member.mention(),
+        name
+    ))
+    .await?;
+    Ok(())
+}
+
+/// remove a user or a role from an exclusion group
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    rename = "remove",
+    check = "is_exclusion_moderator"
+)]
+pub async fn group_remove(
+    ctx: Context<'_>,
+    #[description = "name of the group"]
+    #[autocomplete = "autocomplete_group"]
+    name: String,
+    #[description = "user that should be removed"] user: Option<serenity::User>,
+    #[description = "role that should be removed"] role: Option<serenity::Role>,
+) -> Result<()> {
+    ctx.defer().await?;
+    let guild_id = ctx.guild_id().unwrap_or_default();
+    let database = &ctx.data().database;
+    let member = group_member(user, role)?;
+    let group_id = get_exclusion_group_id(database, guild_id.into(), &name).await?;
+    remove_exclusion_group_member(database, group_id, member).await?;
+    add_group_audit(
+        database,
+        guild_id.into(),
+        ctx.author().id.0,
+        "group remove",
+        &name,
+        Some(member),
+    )
+    .await?;
+    ctx.data().text_policies.invalidate(guild_id).await;
+    // revokes the overwrites that aren't needed anymore
+    sync_group_exclusions(ctx.serenity_context(), ctx.data(), guild_id, group_id).await?;
+    ctx.say(format!(
+        "Removed {} from the group `{}`",
+        member.mention(),
+        name
+    ))
+    .await?;
+    Ok(())
+}
+
+/// delete an exclusion group
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    rename = "delete",
+    check = "is_exclusion_moderator"
+)]
+pub async fn group_delete(
+    ctx: Context<'_>,
+    #[description = "name of the group"]
+    #[autocomplete = "autocomplete_group"]
+    name: String,
+) -> Result<()> {
+    ctx.defer().await?;
+    let guild_id = ctx.guild_id().unwrap_or_default();
+    let database = &ctx.data().database;
+    let group_id = get_exclusion_group_id(database, guild_id.into(), &name).await?;
+    delete_exclusion_group(database, group_id).await?;
+    add_group_audit(
+        database,
+        guild_id.into(),
+        ctx.author().id.0,
+        "group delete",
+        &name,
+        None,
+    )
+    .await?;
+    ctx.data().text_policies.invalidate(guild_id).await;
+    // the group has no exclusions anymore, so all of its overwrites are revoked
+    sync_group_exclusions(ctx.serenity_context(), ctx.data(), guild_id, group_id).await?;
+    ctx.say(format!("Deleted the group `{}`", name)).await?;
+    Ok(())
+}
+
+/// list all exclusion groups on this server
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    rename = "list",
+    check = "is_exclusion_moderator"
+)]
+pub async fn group_list(ctx: Context<'_>) -> Result<()> {
+    let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
+    let groups = get_exclusion_groups(&ctx.data().database, guild_id as i64).await?;
+    ctx.send(|m| {
+        m.embed(|embed| {
+            embed
+                .title("All exclusion groups on this server")
+                .color(crate::color())
+                .description(
+                    "Every member of a group can't see the other members.
+                    Members of a role are not excluded from each other.",
+                );
+            for group in groups.iter() {
+                let members = group
+                    .members
+                    .iter()
+                    .map(|member| member.mention())
+                    .collect::<Vec<String>>();
+                embed.field(
+                    &group.name,
+                    if members.is_empty() {
+                        String::from("no members")
+                    } else {
+                        members.join(", ")
+                    },
+                    false,
+                );
+            }
+            embed
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum ExportFormat {
+    #[name = "json"]
+    Json,
+    #[name = "csv"]
+    Csv,
+}
+
+/// one exclusion in an export file
+#[derive(Serialize, Deserialize)]
+struct ExclusionRow {
+    user_1: u64,
+    user_2: u64,
+    ignoring_user: Option<u64>,
+    reason: Option<String>,
+    expires_at: Option<i64>,
+}
+
+impl From<&Exclusion> for ExclusionRow {
+    fn from(exclusion: &Exclusion) -> Self {
+        let (user_1, user_2) = *exclusion.users();
+        ExclusionRow {
+            user_1,
+            user_2,
+            ignoring_user: match exclusion.direction() {
+                ExclusionDirection::OneWay(user) => Some(user),
+                ExclusionDirection::Mutual => None,
+            },
+            reason: exclusion.reason().map(String::from),
+            expires_at: exclusion.expires_at().map(|date| date.timestamp()),
+        }
+    }
+}
+
+impl ExclusionRow {
+    fn to_exclusion(&self) -> Exclusion {
+        let direction = match self.ignoring_user {
+            Some(user) => ExclusionDirection::OneWay(user),
+            None => ExclusionDirection::Mutual,
+        };
+        Exclusion::from((self.user_1, self.user_2))
+            .with_direction(direction)
+            .with_reason(self.reason.clone())
+            .with_expiry(self.expires_at.map(from_timestamp))
+    }
+}
+
+/// export all exclusions of this server as a file
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    check = "is_exclusion_moderator"
+)]
+pub async fn export(
+    ctx: Context<'_>,
+    #[description = "format of the file, json by default"] format: Option<ExportFormat>,
+) -> Result<()> {
+    let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
+    let rows = get_user_exclusions(&ctx.data().database, guild_id as i64)
+        .await?
+        .iter()
+        .map(ExclusionRow::from)
+        .collect::<Vec<ExclusionRow>>();
+    let (data, filename) = match format.unwrap_or(ExportFormat::Json) {
+        ExportFormat::Json => (serde_json::to_vec_pretty(&rows)?, "exclusions.json"),
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(vec![]);
+            for row in &rows {
+                writer.serialize(row)?;
+            }
+            (writer.into_inner()?, "exclusions.csv")
+        }
+    };
+    ctx.send(|m| {
+        m.content(format!("Exported {} exclusions", rows.len()))
+            .attachment(serenity::AttachmentType::Bytes {
+                data: Cow::Owned(data),
+                filename: String::from(filename),
+            })
+            .ephemeral(true)
+    })
+    .await?;
+    Ok(())
+}
+
+/// import exclusions from a file created with the export
+#[poise::command(
+    track_edits,
+    slash_command,
+    guild_only,
+    check = "is_exclusion_moderator"
+)]
+pub async fn import(
+    ctx: Context<'_>,
+    #[description = "json or csv file of an export"] file: serenity::Attachment,
+) -> Result<()> {
+    ctx.defer().await?;
+    let guild_id = ctx.guild_id().unwrap_or_default();
+    let content = file.download().await?;
+    let rows: Vec<ExclusionRow> = if file.filename.ends_with(".csv") {
+        csv::Reader::from_reader(content.as_slice())
+            .deserialize()
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| AYError::InvalidInput("the file isn't a valid exclusion csv"))?
+    } else {
+        serde_json::from_slice(&content)
+            .map_err(|_| AYError::InvalidInput("the file isn't a valid exclusion json"))?
+    };
+
+    let existing = get_user_exclusions(&ctx.data().database, guild_id.0 as i64)
+        .await?
+        .iter()
+        .map(|exclusion| *exclusion.users())
+        .collect::<HashSet<(u64, u64)>>();
+    let mut seen = HashSet::new();
+    let mut members = HashSet::new();
+    let mut exclusions = vec![];
+    let mut skipped = vec![];
+    for row in rows {
+        let exclusion = row.to_exclusion();
+        let (user_1, user_2) = *exclusion.users();
+        let reason = if user_1 == user_2 {
+            Some("can't exclude a user from themself")
+        } else if row
+            .ignoring_user
+            .map_or(false, |user| exclusion.partner(user).is_none())
+        {
+            Some("the ignoring user isn't part of the exclusion")
+        } else if existing.contains(&(user_1, user_2)) {
+            Some("already excluded")
+        } else if !seen.insert((user_1, user_2)) {
+            Some("duplicate in file")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            skipped.push(format!("<@{}> and <@{}>: {}", user_1, user_2, reason));
+            continue;
+        }
+        let mut missing = false;
+        for user in [user_1, user_2] {
+            if members.contains(&user) {
+                continue;
+            }
+            if guild_id.member(ctx.serenity_context(), user).await.is_ok() {
+                members.insert(user);
+            } else {
+                missing = true;
+            }
+        }
+        if missing {
+            skipped.push(format!(
+                "<@{}> and <@{}>: not a member of this server",
+                user_1, user_2
+            ));
+            continue;
+        }
+        exclusions.push(exclusion);
+    }
+
+    if exclusions.is_empty() {
+        let embed = embed_exclusion_import(&exclusions, &skipped);
+        ctx.send(|m| {
+            m.embed(|e| {
+                e.clone_from(&embed);
+                e
+            })
+        })
+        .await?;
+        return Ok(());
+    }
+
+    let confirm_action = menu::Control::new(
+        menu::MenuComponent::button("confirm", |button| {
+            button.style(serenity::ButtonStyle::Success).label("import")
+        }),
+        Arc::new(|menu, mci| Box::pin(confirm_import(menu, mci))),
+    );
+    let cancel_action = menu::Control::new(
+        menu::MenuComponent::button("cancel", |button| {
+            button.style(serenity::ButtonStyle::Danger).label("cancel")
+        }),
+        Arc::new(|menu, mci| Box::pin(cancel_import(menu, mci))),
+    );
+
+    let embed = embed_exclusion_import(&exclusions, &skipped);
+    let mut menu = Menu::new(&ctx, exclusions, |options| {
+        options.add_row(|row| row.add_button(confirm_action).add_button(cancel_action))
+    });
+    menu.run(|m| {
+        m.embed(|e| {
+            e.clone_from(&embed);
+            e
+        })
+    })
+    .await
+}
+
+/// the function to store the previewed exclusions of the import
+async fn confirm_import(
+    m: &mut Menu<'_, Vec<Exclusion>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<()> {
+    if !is_menu_author(m, mci).await? {
+        return Ok(());
+    }
+    let guild_id = m.ctx.guild_id().unwrap_or_default();
+    let imported = import_user_exclusions(
+        &m.ctx.data().database,
+        guild_id.0 as i64,
+        mci.user.id.0,
+        &m.data,
+    )
+    .await?;
+    m.ctx.data().text_policies.invalidate(guild_id).await;
+    sync_guild_exclusions(m.ctx.serenity_context(), m.ctx.data(), guild_id).await?;
+    m.ctx
+        .say(format!("Imported {} exclusions", imported))
+        .await?;
+    m.stop();
+    Ok(())
+}
+
+/// the function to discard the previewed exclusions of the import
+async fn cancel_import(
+    m: &mut Menu<'_, Vec<Exclusion>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<()> {
+    if !is_menu_author(m, mci).await? {
+        return Ok(());
+    }
+    m.stop();
+    Ok(())
+}
+
+pub fn embed_exclusion_import(
+    exclusions: &[Exclusion],
+    skipped: &[String],
+) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    let mut description = exclusions
+        .iter()
+        .take(SYNC_PREVIEW_SIZE)
+        .map(|exclusion| {
+            let (id_1, id_2) = exclusion.users();
+            match exclusion.direction() {
+                ExclusionDirection::Mutual => format!("<@{}> and <@{}>", id_1, id_2),
+                ExclusionDirection::OneWay(user) => format!(
+                    "<@{}> ignores <@{}>",
+                    user,
+                    exclusion.partner(user).unwrap_or_default()
+                ),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    if exclusions.len() > SYNC_PREVIEW_SIZE {
+        description.push_str(&format!(
+            "\n... and {} more",
+            exclusions.len() - SYNC_PREVIEW_SIZE
+        ));
+    }
+    embed
+        .title(format!("{} exclusions to import", exclusions.len()))
+        .description(description)
+        .color(crate::color());
+    if !skipped.is_empty() {
+        let mut text = skipped
+            .iter()
+            .take(IMPORT_SKIPPED_SIZE)
+            .cloned()
+            .collect::<Vec<String>>()
+            .join("\n");
+        if skipped.len() > IMPORT_SKIPPED_SIZE {
+            text.push_str(&format!(
+                "\n... and {} more",
+                skipped.len() - IMPORT_SKIPPED_SIZE
+            ));
+        }
+        embed.field(format!("{} skipped", skipped.len()), text, false);
+    }
+    embed
+}
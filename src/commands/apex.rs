@@ -1,91 +1,724 @@
 use std::sync::Arc;
 
 use crate::{
-    apex_client,
+    apex::{
+        get_apex_account, link_apex_account, subscribe_map, unlink_apex_account, unsubscribe_map,
+        ApexAccount, CraftingBundle, MapMode, Platform, Player, PredatorThresholds, ServerStatus,
+    },
     error::Error as AYError,
-    menu::{self, Menu},
-    util, Context, Error,
+    menu::{self, set_button, Menu},
+    util::{self, from_timestamp, to_relative_timestamp, to_short_timestamp},
+    Context, Error,
 };
 use apex_rs::model::Map;
-use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::{self as serenity, Mentionable};
 
-/// get the current map rotation for battle royal in apex legends
+/// the id of the button that shows the mode
+fn mode_button_id(mode: MapMode) -> String {
+    format!("mode_{}", mode.key())
+}
+
+/// the current and next map of a mode
+pub struct ModeMaps<'a> {
+    pub mode: MapMode,
+    pub current: &'a Map,
+    pub next: &'a Map,
+}
+
+/// what the maps menu currently shows
+#[derive(Clone, Copy, PartialEq)]
+enum MapsShown {
+    /// the current and next map of every mode
+    Overview,
+    Current(usize),
+    Next(usize),
+}
+
+/// the state of the maps menu
+pub struct MapsView<'a> {
+    maps: Vec<ModeMaps<'a>>,
+    shown: MapsShown,
+}
+
+impl<'a> MapsView<'a> {
+    /// the index of the mode that is shown, none in the overview
+    fn index(&self) -> Option<usize> {
+        match self.shown {
+            MapsShown::Overview => None,
+            MapsShown::Current(index) | MapsShown::Next(index) => Some(index),
+        }
+    }
+
+    fn embed(&self) -> serenity::CreateEmbed {
+        match self.shown {
+            MapsShown::Overview => embed_maps(&self.maps),
+            MapsShown::Current(index) => embed_mode_map(&self.maps[index], false),
+            MapsShown::Next(index) => embed_mode_map(&self.maps[index], true),
+        }
+    }
+
+    /// the rows of buttons, the overview has a button for every mode
+    fn components(&self) -> Vec<Vec<menu::MenuComponent>> {
+        let mut rows = vec![];
+        if self.maps.len() > 1 {
+            rows.push(
+                self.maps
+                    .iter()
+                    .enumerate()
+                    .map(|(index, maps)| create_mode_button(maps.mode, self.index() == Some(index)))
+                    .collect(),
+            );
+        }
+        let mut row = vec![
+            create_current_button(self.index().is_none()),
+            create_next_button(self.index().is_none()),
+        ];
+        if self.maps.len() > 1 {
+            row.push(create_overview_button(self.shown == MapsShown::Overview));
+        }
+        rows.push(row);
+        rows
+    }
+}
+
+/// the apex legends commands, the map rotation is shown by `/maps`
+#[poise::command(
+    slash_command,
+    track_edits,
+    category = "Apex",
+    subcommands(
+        "map_subscribe",
+        "map_unsubscribe",
+        "stats",
+        "link",
+        "unlink",
+        "compare",
+        "crafting",
+        "predator",
+        "status"
+    )
+)]
+pub async fn apex(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// get the current map rotation in apex legends
 #[poise::command(track_edits, slash_command, category = "Apex")]
-pub async fn maps(ctx: Context<'_>) -> Result<(), Error> {
-    // defer to let discord now that the command worked
+pub async fn maps(
+    ctx: Context<'_>,
+    #[description = "mode of the maps, battle royale by default"] mode: Option<MapMode>,
+) -> Result<(), Error> {
+    show_maps(ctx, mode.unwrap_or(MapMode::BattleRoyal)).await
+}
+
+/// get notified in this channel when the rotation switches to a map
+#[poise::command(
+    slash_command,
+    track_edits,
+    category = "Apex",
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS",
+    rename = "subscribe"
+)]
+pub async fn map_subscribe(
+    ctx: Context<'_>,
+    #[description = "name of the map, like World's Edge"] map: String,
+    #[description = "mode of the rotation, battle royale by default"] mode: Option<MapMode>,
+    #[description = "role to mention"] role: Option<serenity::Role>,
+    #[description = "user to mention"] user: Option<serenity::User>,
+) -> Result<(), Error> {
+    let mode = mode.unwrap_or(MapMode::BattleRoyal);
+    let mentions = [
+        role.map(|role| role.mention().to_string()),
+        user.map(|user| user.mention().to_string()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<String>>()
+    .join(" ");
+    let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
+    subscribe_map(
+        &ctx.data().database,
+        guild_id as i64,
+        ctx.channel_id().0 as i64,
+        &map,
+        mode,
+        Some(mentions.as_str()).filter(|mentions| !mentions.is_empty()),
+    )
+    .await?;
+    ctx.say(format!(
+        "This channel gets a message when {} is played in {}",
+        map.trim(),
+        mode.name()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// stop the notifications about a map in this channel
+#[poise::command(
+    slash_command,
+    track_edits,
+    category = "Apex",
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS",
+    rename = "unsubscribe"
+)]
+pub async fn map_unsubscribe(
+    ctx: Context<'_>,
+    #[description = "name of the map, like World's Edge"] map: String,
+    #[description = "mode of the rotation, every mode by default"] mode: Option<MapMode>,
+) -> Result<(), Error> {
+    unsubscribe_map(&ctx.data().database, ctx.channel_id().0 as i64, &map, mode).await?;
+    ctx.say(format!(
+        "This channel doesn't get messages about {} anymore",
+        map.trim()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// shows the level, rank, legend and trackers of a player, your linked account by default
+#[poise::command(slash_command, track_edits, category = "Apex")]
+pub async fn stats(
+    ctx: Context<'_>,
+    #[description = "platform of the player"] platform: Option<Platform>,
+    #[description = "name of the player"] player: Option<String>,
+) -> Result<(), Error> {
     ctx.defer().await?;
-    // the map rotations
-    let rotation = apex_client()?.battle_royal_rotation().await?;
+    let account = resolve_account(ctx, platform, player).await?;
+    let player = ctx
+        .data()
+        .apex_cache
+        .player(account.platform, &account.player)
+        .await?;
+    ctx.send(|m| {
+        m.embed(|e| {
+            e.clone_from(&embed_player(&player));
+            e
+        })
+    })
+    .await?;
+    Ok(())
+}
 
-    // get the current and next map for battle royal
-    // return error if a map is unavailable
-    // invalid input to inform user that the apex api is not reachable
-    let (current, next) = (
-        rotation.current().map_or(
-            Err(AYError::InvalidInput("maps are currently not available")),
-            |map| Ok(map),
-        )?,
-        rotation.next().map_or(
-            Err(AYError::InvalidInput("maps are currently not available")),
-            |map| Ok(map),
-        )?,
-    );
-
-    // button and control function for the current map
-    let button_current = menu::MenuComponent::button("current", |button| {
-        button
-            .style(serenity::ButtonStyle::Primary)
-            .label("current")
+/// link your apex account to show your stats without a name
+#[poise::command(slash_command, track_edits, category = "Apex")]
+pub async fn link(
+    ctx: Context<'_>,
+    #[description = "your platform"] platform: Platform,
+    #[description = "your name in apex legends"] player: String,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    // only existing players can be linked
+    let player = ctx.data().apex_cache.player(platform, &player).await?;
+    let account = ApexAccount {
+        platform,
+        player: player.global.name.clone(),
+    };
+    link_apex_account(&ctx.data().database, ctx.author().id.0 as i64, &account).await?;
+    ctx.send(|m| {
+        m.content(format!("Linked you to {}", account.player))
+            .ephemeral(true)
+    })
+    .await?;
+    Ok(())
+}
+
+/// remove the link to your apex account
+#[poise::command(slash_command, track_edits, category = "Apex")]
+pub async fn unlink(ctx: Context<'_>) -> Result<(), Error> {
+    unlink_apex_account(&ctx.data().database, ctx.author().id.0 as i64).await?;
+    ctx.send(|m| {
+        m.content("Your apex account isn't linked anymore")
+            .ephemeral(true)
+    })
+    .await?;
+    Ok(())
+}
+
+/// compare the stats of two players, the second one is your linked account by default
+#[poise::command(slash_command, track_edits, category = "Apex")]
+pub async fn compare(
+    ctx: Context<'_>,
+    #[description = "platform of the first player"] platform: Platform,
+    #[description = "name of the first player"] player: String,
+    #[description = "platform of the second player"] other_platform: Option<Platform>,
+    #[description = "name of the second player"] other_player: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let other = resolve_account(ctx, other_platform, other_player).await?;
+    let cache = &ctx.data().apex_cache;
+    let first = cache.player(platform, &player).await?;
+    let second = cache.player(other.platform, &other.player).await?;
+    ctx.send(|m| {
+        m.embed(|e| {
+            e.clone_from(&embed_compare(&first, &second));
+            e
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// shows what the replicator can craft right now
+#[poise::command(slash_command, track_edits, category = "Apex")]
+pub async fn crafting(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer().await?;
+    let bundles = ctx.data().apex_cache.crafting().await?;
+    ctx.send(|m| {
+        m.embed(|e| {
+            e.clone_from(&embed_crafting(&bundles));
+            e
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// shows how many points are needed for predator on every platform
+#[poise::command(slash_command, track_edits, category = "Apex")]
+pub async fn predator(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer().await?;
+    let thresholds = ctx.data().apex_cache.predator().await?;
+    ctx.send(|m| {
+        m.embed(|e| {
+            e.clone_from(&embed_predator(&thresholds));
+            e
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// shows if the servers of apex legends are up
+#[poise::command(slash_command, track_edits, category = "Apex")]
+pub async fn status(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer().await?;
+    let status = ctx.data().apex_cache.server_status().await?;
+    ctx.send(|m| {
+        m.embed(|e| {
+            e.clone_from(&embed_server_status(&status));
+            e
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// the given account or the linked account of the author
+async fn resolve_account(
+    ctx: Context<'_>,
+    platform: Option<Platform>,
+    player: Option<String>,
+) -> Result<ApexAccount, Error> {
+    match (platform, player) {
+        (Some(platform), Some(player)) => Ok(ApexAccount { platform, player }),
+        (None, None) => Ok(
+            get_apex_account(&ctx.data().database, ctx.author().id.0 as i64)
+                .await?
+                .ok_or(AYError::InvalidInput(
+                    "link your apex account or give a platform and a player",
+                ))?,
+        ),
+        _ => Err(Box::new(AYError::InvalidInput(
+            "a player needs a platform and a name",
+        ))),
+    }
+}
+
+/// the level, rank, legend and trackers of the player
+pub fn embed_player(player: &Player) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    embed
+        .title(format!(
+            "{} ({})",
+            player.global.name, player.global.platform
+        ))
+        .color(crate::color())
+        .field(
+            "Level",
+            format!(
+                "{} ({:.0}% to the next)",
+                player.global.level, player.global.to_next_level_percent
+            ),
+            true,
+        );
+    if let Some(rank) = player.rank() {
+        embed.field("Rank", rank, true);
+    }
+    if let Some(realtime) = &player.realtime {
+        embed.field("Status", &realtime.state, true);
+    }
+    if let Some(legend) = player.legend() {
+        let trackers = legend
+            .data
+            .iter()
+            .map(|tracker| format!("{}: {}", tracker.name, tracker.value))
+            .collect::<Vec<String>>();
+        embed.field(
+            format!("Legend: {}", legend.name),
+            if trackers.is_empty() {
+                String::from("no trackers equipped")
+            } else {
+                trackers.join("\n")
+            },
+            false,
+        );
+        if let Some(icon) = legend
+            .assets
+            .as_ref()
+            .and_then(|assets| assets.icon.as_ref())
+        {
+            embed.thumbnail(icon);
+        }
+    }
+    embed
+}
+
+/// the stats of two players next to each other
+pub fn embed_compare(first: &Player, second: &Player) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    embed
+        .title(format!("{} vs. {}", first.global.name, second.global.name))
+        .color(crate::color());
+    for player in [first, second] {
+        let mut text = vec![format!("Level: {}", player.global.level)];
+        if let Some(rank) = player.rank() {
+            text.push(format!("Rank: {}", rank));
+        }
+        if let Some(legend) = player.legend() {
+            text.push(format!("Legend: {}", legend.name));
+            for tracker in legend.data.iter() {
+                text.push(format!("{}: {}", tracker.name, tracker.value));
+            }
+        }
+        embed.field(&player.global.name, text.join("\n"), true);
+    }
+    // the level is the only stat that every player has
+    let difference = first.global.level - second.global.level;
+    embed.footer(|f| {
+        f.text(match difference {
+            0 => String::from("both have the same level"),
+            difference if difference > 0 => {
+                format!("{} is {} levels ahead", first.global.name, difference)
+            }
+            difference => format!("{} is {} levels ahead", second.global.name, -difference),
+        })
     });
-    let action_current = menu::Control::new(
-        button_current,
-        Arc::new(|menu, mci| Box::pin(select_current(menu, mci))),
-    );
-
-    // button and control function for the next map
-    let next_button = menu::MenuComponent::button("next", |button| {
-        button.style(serenity::ButtonStyle::Primary).label("next")
+    embed
+}
+
+/// the items of the replicator by bundle
+pub fn embed_crafting(bundles: &[CraftingBundle]) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    embed.title("Replicator").color(crate::color());
+    for bundle in bundles {
+        let mut text = bundle
+            .content
+            .iter()
+            .map(|item| {
+                format!(
+                    "{} ({}): {} materials",
+                    item.item_type.name, item.item_type.rarity, item.cost
+                )
+            })
+            .collect::<Vec<String>>();
+        // discord rejects fields without a value
+        if text.is_empty() {
+            text.push(String::from("nothing to craft"));
+        }
+        if let Some(end) = bundle.end {
+            text.push(format!(
+                "changes {}",
+                to_relative_timestamp(&from_timestamp(end))
+            ));
+        }
+        embed.field(&bundle.kind, text.join("\n"), false);
+    }
+    embed
+}
+
+/// the points needed for predator in battle royale and arenas
+pub fn embed_predator(thresholds: &PredatorThresholds) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    embed.title("Predator").color(crate::color());
+    for (mode, points, thresholds) in [
+        ("Battle Royale", "RP", &thresholds.battle_royal),
+        ("Arenas", "AP", &thresholds.arenas),
+    ] {
+        if thresholds.is_empty() {
+            continue;
+        }
+        let text = thresholds
+            .iter()
+            .map(|(platform, threshold)| {
+                format!(
+                    "{}: {} {} ({} masters and predators)",
+                    Platform::from_key(platform)
+                        .map_or(platform.as_str(), |platform| platform.name()),
+                    threshold.value,
+                    points,
+                    threshold.masters_and_predators
+                )
+            })
+            .collect::<Vec<String>>();
+        embed.field(mode, text.join("\n"), false);
+    }
+    embed
+}
+
+/// the status of every region of every service
+pub fn embed_server_status(status: &ServerStatus) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    embed.title("Server status").color(crate::color());
+    let mut down = 0;
+    // discord rejects fields without a value
+    for (service, regions) in status.iter().filter(|(_, regions)| !regions.is_empty()) {
+        let text = regions
+            .iter()
+            .map(|(region, status)| {
+                let icon = match status.status.as_str() {
+                    "UP" => "🟢",
+                    "SLOW" => "🟡",
+                    _ => {
+                        down += 1;
+                        "🔴"
+                    }
+                };
+                format!("{} {}", icon, region)
+            })
+            .collect::<Vec<String>>();
+        embed.field(service.replace('_', " "), text.join("\n"), true);
+    }
+    embed.description(match down {
+        0 => String::from("all servers are up"),
+        down => format!("{} servers are down", down),
     });
-    let next_action = menu::Control::new(
-        next_button,
-        Arc::new(|menu, mci| Box::pin(select_next(menu, mci))),
-    );
+    embed
+}
+
+async fn show_maps(ctx: Context<'_>, mode: MapMode) -> Result<(), Error> {
+    // defer to let discord now that the command worked
+    ctx.defer().await?;
+    let modes = match mode {
+        MapMode::All => MapMode::MODES.to_vec(),
+        mode => vec![mode],
+    };
+
+    // the map rotations of the modes, shared with every other invocation
+    let mut rotations = vec![];
+    for mode in modes {
+        rotations.push(ctx.data().apex_cache.rotation(mode).await?);
+    }
+
+    // get the current and next map of the modes
+    // modes without maps, like a limited time mode that isn't running, are skipped
+    let maps = rotations
+        .iter()
+        .filter_map(|rotation| match (&rotation.current, &rotation.next) {
+            (Some(current), Some(next)) => Some(ModeMaps {
+                mode: rotation.mode,
+                current,
+                next,
+            }),
+            _ => None,
+        })
+        .collect::<Vec<ModeMaps<'_>>>();
+    // invalid input to inform user that the apex api is not reachable
+    if maps.is_empty() {
+        return Err(Box::new(AYError::InvalidInput(
+            "maps are currently not available",
+        )));
+    }
+
+    let view = MapsView {
+        shown: if mode == MapMode::All {
+            MapsShown::Overview
+        } else {
+            MapsShown::Current(0)
+        },
+        maps,
+    };
+    let embed = view.embed();
+
+    // every button of the view gets its control function
+    let rows = view
+        .components()
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|button| create_control(&view, button))
+                .collect::<Vec<menu::Control<MapsView<'_>>>>()
+        })
+        .collect::<Vec<_>>();
 
     // create the menu with the buttons
-    let mut menu = menu::Menu::new(&ctx, (current, next), |options| {
-        options.add_row(|row| row.add_button(action_current).add_button(next_action))
+    let mut menu = menu::Menu::new(&ctx, view, |options| {
+        for row in rows {
+            options.add_row(|create| {
+                for control in row {
+                    create.add_button(control);
+                }
+                create
+            });
+        }
+        options
     });
 
-    // run the menu with the current map as default
+    // run the menu with the current map or the overview as default
     menu.run(|m| {
         m.embed(|e| {
-            e.clone_from(&util::embed_map(current, false));
+            e.clone_from(&embed);
             e
         })
     })
     .await
 }
 
+/// the current and next map of every mode in one embed
+pub fn embed_maps(maps: &[ModeMaps<'_>]) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    embed.title("Map rotation").color(crate::color());
+    for maps in maps {
+        embed.field(
+            maps.mode.name(),
+            format!(
+                "**{}** until {}\nthen **{}** {}",
+                maps.current.name(),
+                to_short_timestamp(maps.current.end_as_date()),
+                maps.next.name(),
+                to_relative_timestamp(&maps.next.start_as_date()),
+            ),
+            false,
+        );
+    }
+    embed
+}
+
+/// the map of a mode, with the mode as title
+fn embed_mode_map(maps: &ModeMaps<'_>, next_map: bool) -> serenity::CreateEmbed {
+    let map = if next_map { maps.next } else { maps.current };
+    let mut embed = util::embed_map(map, next_map);
+    embed.title(maps.mode.name());
+    embed
+}
+
+/// the button with the function that matches its id
+fn create_control<'a>(
+    view: &MapsView<'a>,
+    button: menu::MenuComponent,
+) -> menu::Control<MapsView<'a>> {
+    let function: menu::ControlFunction<MapsView<'a>> = match button.id().as_str() {
+        "current" => Arc::new(|menu, mci| Box::pin(select_current(menu, mci))),
+        "next" => Arc::new(|menu, mci| Box::pin(select_next(menu, mci))),
+        "overview" => Arc::new(|menu, mci| Box::pin(select_overview(menu, mci))),
+        id => {
+            let index = view
+                .maps
+                .iter()
+                .position(|maps| mode_button_id(maps.mode) == id)
+                .unwrap_or_default();
+            Arc::new(move |menu, mci| Box::pin(select_mode(menu, mci, index)))
+        }
+    };
+    menu::Control::new(button, function)
+}
+
+fn create_mode_button(mode: MapMode, shown: bool) -> menu::MenuComponent {
+    menu::MenuComponent::button(&mode_button_id(mode), |button| {
+        button
+            .style(if shown {
+                serenity::ButtonStyle::Success
+            } else {
+                serenity::ButtonStyle::Secondary
+            })
+            .label(mode.name())
+    })
+}
+
+fn create_current_button(disabled: bool) -> menu::MenuComponent {
+    menu::MenuComponent::button("current", |button| {
+        button
+            .style(serenity::ButtonStyle::Primary)
+            .label("current")
+            .disabled(disabled)
+    })
+}
+
+fn create_next_button(disabled: bool) -> menu::MenuComponent {
+    menu::MenuComponent::button("next", |button| {
+        button
+            .style(serenity::ButtonStyle::Primary)
+            .label("next")
+            .disabled(disabled)
+    })
+}
+
+fn create_overview_button(shown: bool) -> menu::MenuComponent {
+    menu::MenuComponent::button("overview", |button| {
+        button
+            .style(serenity::ButtonStyle::Secondary)
+            .label("overview")
+            .disabled(shown)
+    })
+}
+
 /// the function to select the current map as display
 async fn select_current(
-    m: &mut Menu<'_, (&Map, &Map)>,
+    m: &mut Menu<'_, MapsView<'_>>,
     mci: &Arc<serenity::MessageComponentInteraction>,
 ) -> Result<(), Error> {
-    let map = m.data.0;
-    m.update_response(|m| m.set_embed(util::embed_map(map, false)), mci)
-        .await?;
-
-    Ok(())
+    if let Some(index) = m.data.index() {
+        m.data.shown = MapsShown::Current(index);
+    }
+    update_message(m, mci).await
 }
 
 /// the function to select the next map as display
 async fn select_next(
-    m: &mut Menu<'_, (&Map, &Map)>,
+    m: &mut Menu<'_, MapsView<'_>>,
     mci: &Arc<serenity::MessageComponentInteraction>,
 ) -> Result<(), Error> {
-    let map = m.data.1;
-    m.update_response(|m| m.set_embed(util::embed_map(map, true)), mci)
-        .await?;
+    if let Some(index) = m.data.index() {
+        m.data.shown = MapsShown::Next(index);
+    }
+    update_message(m, mci).await
+}
+
+/// the function to select the maps of all modes as display
+async fn select_overview(
+    m: &mut Menu<'_, MapsView<'_>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<(), Error> {
+    m.data.shown = MapsShown::Overview;
+    update_message(m, mci).await
+}
 
+/// the function to select the current map of a mode as display
+async fn select_mode(
+    m: &mut Menu<'_, MapsView<'_>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+    index: usize,
+) -> Result<(), Error> {
+    m.data.shown = MapsShown::Current(index);
+    update_message(m, mci).await
+}
+
+async fn update_message(
+    m: &mut Menu<'_, MapsView<'_>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<(), Error> {
+    let embed = m.data.embed();
+    let mut components = serenity::CreateComponents::default();
+    for buttons in m.data.components() {
+        let mut row = serenity::CreateActionRow::default();
+        for button in buttons.iter() {
+            set_button(&mut row, button);
+        }
+        components.add_action_row(row);
+    }
+    m.update_response(|m| m.set_embed(embed).set_components(components), mci)
+        .await?;
     Ok(())
 }
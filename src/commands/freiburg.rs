@@ -2,66 +2,593 @@ use std::{collections::HashMap, fmt::Display, sync::Arc};
 
 use crate::{
     error::Error as AYError,
-    menu::{self, set_button, Menu},
-    util::create_mensa_plan_by_day,
+    mensa::{
+        current_week, date_of_day, embed_mensa_day, embed_mensa_week, fuzzy_matches,
+        get_dish_ratings, get_menu_filter, get_price_tier, get_top_dishes, parse_allergens,
+        plan_day, plan_week, rate_dish, resolve_mensa_place, set_guild_mensa_place,
+        set_menu_filter, set_price_tier, set_user_mensa_place, subscribe_mensa, unsubscribe_mensa,
+        upcoming_week, Diet, Dish, DishRating, MensaPlan, MenuFilter, PriceTier, DEFAULT_PLACE,
+    },
+    menu::{self, set_button, Cursor, Menu},
     Context, Error,
 };
 use chrono::Datelike;
-use mensa_fr::{mensa::Plan, mensa::Weekday, MensaPlace, UrlBuilder};
+use chrono_tz::Europe::Berlin;
+use mensa_fr::{
+    mensa::{Plan, Weekday},
+    MensaPlace,
+};
 use mensa_swfr_rs as mensa_fr;
 use poise::serenity_prelude as serenity;
 use strum::IntoEnumIterator;
 
+const SEARCH_PAGE_SIZE: usize = 6;
+const TOP_DISHES_SIZE: i64 = 10;
+
 // TODO: there must be a simpler solution to set the selection of the select menus
 // works for now, but not my proudest work
 
-const DEFAULT_PLACE: MensaPlace = MensaPlace::Rempartstraße;
+/// mensa plans for freiburg, the plan of today is `/mensa plan`
+///
+/// the mensa of the plan is chosen with the `place` option of `/mensa plan`,
+/// `/mensa place` saves the mensa that is shown without it
+#[poise::command(
+    slash_command,
+    track_edits,
+    category = "University Freiburg",
+    subcommands(
+        "plan",
+        "diet",
+        "price",
+        "place",
+        "default",
+        "search",
+        "subscribe",
+        "unsubscribe",
+        "top"
+    )
+)]
+pub async fn mensa(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
 
-/// shows all mensa plans for freiburg
+/// shows the mensa plan of today, of any mensa with `place`
 #[poise::command(slash_command, track_edits, category = "University Freiburg")]
-pub async fn mensa(ctx: Context<'_>) -> Result<(), Error> {
+pub async fn plan(
+    ctx: Context<'_>,
+    #[description = "mensa of the plan, your saved mensa by default"]
+    #[autocomplete = "autocomplete_place"]
+    place: Option<String>,
+    #[description = "only show dishes of this diet, your saved diet by default"] diet: Option<Diet>,
+    #[description = "comma separated allergens to hide, like gluten, milk"] exclude: Option<String>,
+) -> Result<(), Error> {
+    let mut filter = get_menu_filter(&ctx.data().database, ctx.author().id.0 as i64).await?;
+    if let Some(diet) = diet {
+        filter.diet = diet;
+    }
+    if let Some(exclude) = exclude {
+        filter.excluded = parse_allergens(&exclude)?;
+    }
+    let place = match place {
+        Some(place) => parse_place(&place)?,
+        None => resolve_place(ctx).await?,
+    };
+    show_mensa(ctx, filter, place).await
+}
+
+/// the dishes of a mensa on one day that match a search
+pub struct SearchGroup {
+    title: String,
+    dishes: Vec<String>,
+}
+
+/// find out which mensa serves a dish this week
+#[poise::command(slash_command, track_edits, category = "University Freiburg")]
+pub async fn search(
+    ctx: Context<'_>,
+    #[description = "dish to search for, like Schnitzel"]
+    #[rest]
+    text: String,
+) -> Result<(), Error> {
+    if text.trim().is_empty() {
+        return Err(Box::new(AYError::InvalidInput("nothing to search for")));
+    }
     ctx.defer().await?;
+    let data = ctx.data();
+    let week = upcoming_week();
+    let mut groups = vec![];
+    for place in MensaPlace::iter() {
+        let mensa = match data.mensa_cache.mensa_plan(&data.database, &place).await {
+            Ok(mensa) => mensa,
+            // one unreachable mensa shouldn't break the search
+            Err(why) => {
+                tracing::warn!("couldn't search mensa plan of {}: {}", place, why);
+                continue;
+            }
+        };
+        for day in plan_week(mensa.plan(), week) {
+            let dishes = day
+                .menues
+                .iter()
+                .filter(|menu| fuzzy_matches(&text, &menu.name))
+                .map(|menu| {
+                    let dish = Dish::parse(&menu.name, menu.food_type.as_deref());
+                    format!("**{}**: {}", menu.art, dish.components.join(", "))
+                })
+                .collect::<Vec<String>>();
+            if !dishes.is_empty() {
+                groups.push(SearchGroup {
+                    title: format!(
+                        "{}, {} ({})",
+                        place,
+                        day.weekday()
+                            .unwrap_or(chrono::Utc::now().weekday().into())
+                            .full_name(),
+                        day.to_chrono().unwrap().format("%d.%m.")
+                    ),
+                    dishes,
+                });
+            }
+        }
+    }
+    if groups.is_empty() {
+        ctx.say(format!("No mensa serves \"{}\" this week", text))
+            .await?;
+        return Ok(());
+    }
+    let pages = groups
+        .chunks(SEARCH_PAGE_SIZE)
+        .map(|page| page.iter().collect())
+        .collect::<Vec<Vec<&SearchGroup>>>();
+
+    let prev_action = menu::Control::new(
+        menu::MenuComponent::button("prev", |button| {
+            button.style(serenity::ButtonStyle::Primary).label("<")
+        }),
+        Arc::new(|menu, mci| Box::pin(select_prev_results(menu, mci))),
+    );
+    let next_action = menu::Control::new(
+        menu::MenuComponent::button("next", |button| {
+            button.style(serenity::ButtonStyle::Primary).label(">")
+        }),
+        Arc::new(|menu, mci| Box::pin(select_next_results(menu, mci))),
+    );
+
+    let mut menu = Menu::new(&ctx, (text, Cursor::from(&pages)), |options| {
+        options.add_row(|row| row.add_button(prev_action).add_button(next_action))
+    });
+    let embed = embed_search_results(&menu.data.0, &menu.data.1);
+    menu.run(|m| {
+        m.embed(|e| {
+            e.clone_from(&embed);
+            e
+        })
+    })
+    .await
+}
+
+/// the function to show the previous page of the search results
+async fn select_prev_results(
+    m: &mut Menu<'_, (String, Cursor<'_, Vec<&SearchGroup>>)>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<(), Error> {
+    m.data.1.prev();
+    let embed = embed_search_results(&m.data.0, &m.data.1);
+    m.update_response(|m| m.set_embed(embed), mci).await?;
+    Ok(())
+}
+
+/// the function to show the next page of the search results
+async fn select_next_results(
+    m: &mut Menu<'_, (String, Cursor<'_, Vec<&SearchGroup>>)>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<(), Error> {
+    m.data.1.next();
+    let embed = embed_search_results(&m.data.0, &m.data.1);
+    m.update_response(|m| m.set_embed(embed), mci).await?;
+    Ok(())
+}
+
+pub fn embed_search_results(
+    text: &str,
+    cursor: &Cursor<'_, Vec<&SearchGroup>>,
+) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    embed
+        .title(format!("Mensa dishes like \"{}\"", text))
+        .color(crate::color())
+        .footer(|f| f.text(format!("page {}/{}", cursor.index() + 1, cursor.len())));
+    for group in cursor.current().into_iter().flatten() {
+        embed.field(&group.title, group.dishes.join("\n"), false);
+    }
+    embed
+}
+
+/// save the mensa that is shown to you by default
+#[poise::command(slash_command, track_edits, category = "University Freiburg")]
+pub async fn place(
+    ctx: Context<'_>,
+    #[description = "your mensa, none to use the mensa of the server"]
+    #[autocomplete = "autocomplete_place"]
+    place: Option<String>,
+) -> Result<(), Error> {
+    let place = place.as_deref().map(parse_place).transpose()?;
+    set_user_mensa_place(&ctx.data().database, ctx.author().id.0 as i64, place).await?;
+    let message = match place {
+        Some(place) => format!("Your mensa plans open on {}", place),
+        None => String::from("Your mensa plans open on the mensa of the server again"),
+    };
+    ctx.send(|m| m.content(message).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// set the mensa that is shown on this server by default
+#[poise::command(
+    slash_command,
+    track_edits,
+    category = "University Freiburg",
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn default(
+    ctx: Context<'_>,
+    #[description = "mensa of the server, none to reset it"]
+    #[autocomplete = "autocomplete_place"]
+    place: Option<String>,
+) -> Result<(), Error> {
+    let place = place.as_deref().map(parse_place).transpose()?;
+    let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
+    set_guild_mensa_place(&ctx.data().database, guild_id as i64, place).await?;
+    let message = format!(
+        "Mensa plans on this server open on {}",
+        place.unwrap_or(DEFAULT_PLACE)
+    );
+    ctx.say(message).await?;
+    Ok(())
+}
+
+/// save the diet that is used for your mensa plans
+#[poise::command(slash_command, track_edits, category = "University Freiburg")]
+pub async fn diet(
+    ctx: Context<'_>,
+    #[description = "only show dishes of this diet"] diet: Diet,
+    #[description = "comma separated allergens to hide, like gluten, milk"] exclude: Option<String>,
+) -> Result<(), Error> {
+    let filter = MenuFilter {
+        diet,
+        excluded: parse_allergens(exclude.as_deref().unwrap_or_default())?,
+    };
+    set_menu_filter(&ctx.data().database, ctx.author().id.0 as i64, &filter).await?;
+    let message = match filter.describe() {
+        Some(description) => format!("Your mensa plans are now filtered: {}", description),
+        None => String::from("Your mensa plans show every dish again"),
+    };
+    ctx.send(|m| m.content(message).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// save the price tier that is shown in your mensa plans
+#[poise::command(slash_command, track_edits, category = "University Freiburg")]
+pub async fn price(
+    ctx: Context<'_>,
+    #[description = "the prices you pay, none to show all prices"] tier: Option<PriceTier>,
+) -> Result<(), Error> {
+    set_price_tier(&ctx.data().database, ctx.author().id.0 as i64, tier).await?;
+    let message = match tier {
+        Some(tier) => format!("Your mensa plans show the {} prices", tier.kind()),
+        None => String::from("Your mensa plans show all prices again"),
+    };
+    ctx.send(|m| m.content(message).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// post the mensa plan to this channel every weekday
+#[poise::command(
+    slash_command,
+    track_edits,
+    category = "University Freiburg",
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn subscribe(
+    ctx: Context<'_>,
+    #[description = "mensa of the plan"]
+    #[autocomplete = "autocomplete_place"]
+    place: String,
+    #[description = "time of the post in Europe/Berlin, like 10:30"] time: String,
+) -> Result<(), Error> {
+    let place = parse_place(&place)?;
+    let post_time = chrono::NaiveTime::parse_from_str(&time, "%H:%M")
+        .map_err(|_| AYError::InvalidInput("time has to look like 10:30"))?;
+    let guild_id: u64 = ctx.guild_id().unwrap_or_default().into();
+    subscribe_mensa(
+        &ctx.data().database,
+        guild_id as i64,
+        ctx.channel_id().0 as i64,
+        &place,
+        &post_time,
+    )
+    .await?;
+    ctx.say(format!(
+        "The plan of {} is posted here every weekday at {}",
+        place,
+        post_time.format("%H:%M")
+    ))
+    .await?;
+    Ok(())
+}
+
+/// stop posting the mensa plan to this channel
+#[poise::command(
+    slash_command,
+    track_edits,
+    category = "University Freiburg",
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn unsubscribe(ctx: Context<'_>) -> Result<(), Error> {
+    unsubscribe_mensa(&ctx.data().database, ctx.channel_id().0 as i64).await?;
+    ctx.say("The mensa plan isn't posted here anymore").await?;
+    Ok(())
+}
+
+/// shows the best rated dishes that are served more often
+#[poise::command(slash_command, track_edits, category = "University Freiburg")]
+pub async fn top(
+    ctx: Context<'_>,
+    #[description = "only rank the dishes of this mensa"]
+    #[autocomplete = "autocomplete_place"]
+    place: Option<String>,
+) -> Result<(), Error> {
+    let place = place.as_deref().map(parse_place).transpose()?;
+    let dishes = get_top_dishes(&ctx.data().database, place.as_ref(), TOP_DISHES_SIZE).await?;
+    if dishes.is_empty() {
+        ctx.say("No dish was rated on more than one day yet")
+            .await?;
+        return Ok(());
+    }
+    let text = dishes
+        .iter()
+        .enumerate()
+        .map(|(rank, dish)| {
+            format!(
+                "{}. **{}** ({}) {:.1}★ from {} ratings on {} days",
+                rank + 1,
+                dish.name,
+                dish.place,
+                dish.rating.average,
+                dish.rating.count,
+                dish.days
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    ctx.send(|m| {
+        m.embed(|e| {
+            e.title(match place {
+                Some(place) => format!("Best dishes of {}", place),
+                None => String::from("Best mensa dishes"),
+            })
+            .description(text)
+            .color(crate::color())
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+fn parse_place(place: &str) -> Result<MensaPlace, Error> {
+    Ok(MensaPlace::try_from(place).map_err(|_| AYError::InvalidInput("unknown mensa"))?)
+}
+
+/// the saved mensa of the author or the server
+async fn resolve_place(ctx: Context<'_>) -> Result<MensaPlace, Error> {
+    let guild_id = ctx.guild_id().map(|guild_id| guild_id.0 as i64);
+    resolve_mensa_place(&ctx.data().database, guild_id, ctx.author().id.0 as i64).await
+}
+
+async fn autocomplete_place<'a>(
+    _ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    let partial = partial.to_lowercase();
+    MensaPlace::iter()
+        .filter(move |place| {
+            place.to_string().to_lowercase().contains(&partial)
+                || place.id().to_string().to_lowercase().contains(&partial)
+        })
+        .map(|place| place.id().to_string())
+}
+
+/// the state of the mensa menu
+pub struct MensaView<'a> {
+    days: Cursor<'a, Weekday>,
+    /// the week of the plan, see `mensa::current_week`
+    week: i32,
+    place: MensaPlace,
+    filter: MenuFilter,
+    /// the price tier of the user, all prices are shown if there is none
+    tier: Option<PriceTier>,
+    all_prices: bool,
+    week_view: bool,
+    /// the dish that is rated by the next rating button and the day it was served on
+    rated: Option<(Dish, String)>,
+}
+
+impl<'a> MensaView<'a> {
+    fn shown_tier(&self) -> Option<PriceTier> {
+        self.tier.filter(|_| !self.all_prices)
+    }
+
+    fn day(&self) -> Weekday {
+        *self.days.current().unwrap()
+    }
+
+    /// shows `weekday` of the shown week
+    fn show_day(&mut self, weekday: Weekday) {
+        // the days of the cursor are all weekdays in order
+        if let Some(index) = Weekday::iter().position(|day| day == weekday) {
+            self.days.select(index);
+        }
+        self.week_view = false;
+        self.rated = None;
+    }
+
+    fn show_place(&mut self, place: MensaPlace) {
+        self.place = place;
+        self.rated = None;
+    }
+
+    /// the dish of the rating select and the day it was served on, or why it can't be rated
+    fn rated_dish(
+        &self,
+        plan: &Plan,
+        value: Option<&String>,
+    ) -> Result<(Dish, String), &'static str> {
+        let served = value
+            .and_then(|index| index.parse::<usize>().ok())
+            .zip(plan_day(plan, self.day(), self.week))
+            .and_then(|(index, day)| day.menues.get(index))
+            .ok_or("That dish isn't on the plan")?;
+        let served_on = self
+            .rated_date()
+            .ok_or("Dishes can't be rated before they are served")?;
+        Ok((
+            Dish::parse(&served.name, served.food_type.as_deref()),
+            served_on,
+        ))
+    }
+
+    /// the date of the shown day if its dishes can be rated, they can't before they are served
+    fn rated_date(&self) -> Option<String> {
+        let date = date_of_day(self.day(), self.week)?;
+        let today = chrono::Utc::now().with_timezone(&Berlin).date_naive();
+        (!self.week_view && date <= today).then(|| date.format("%Y-%m-%d").to_string())
+    }
+
+    fn embed(
+        &self,
+        mensa: &MensaPlan,
+        ratings: &HashMap<String, DishRating>,
+    ) -> serenity::CreateEmbed {
+        if self.week_view {
+            embed_mensa_week(mensa, self.week, &self.filter, self.shown_tier())
+        } else {
+            embed_mensa_day(
+                mensa,
+                self.place,
+                self.day(),
+                self.week,
+                &self.filter,
+                self.shown_tier(),
+                ratings,
+            )
+        }
+    }
+}
+
+async fn show_mensa(ctx: Context<'_>, filter: MenuFilter, place: MensaPlace) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let tier = get_price_tier(&ctx.data().database, ctx.author().id.0 as i64).await?;
+    let mensa_today = ctx
+        .data()
+        .mensa_cache
+        .mensa_plan(&ctx.data().database, &place)
+        .await?;
 
-    let token = crate::CONFIG.swfr_token.clone().map_or(
-        Err(AYError::InvalidInput("no mensa swfr token in config")),
-        |token| Ok(token),
-    )?;
-    let mut mensa_cache = MensaCache::new(&token);
-    let weekday_today = chrono::Utc::now().weekday();
-    let mensa_today = mensa_cache.mensa_plan(&DEFAULT_PLACE).await?;
-    let embed = mensa_today
-        .day(weekday_today.into())
-        .map(|day| create_mensa_plan_by_day(day))
-        .unwrap_or({
-            let mut embed = serenity::CreateEmbed::default();
-            embed.description("no mensa today").color(crate::color());
-            embed
-        });
+    let weekdays = Weekday::iter().collect::<Vec<Weekday>>();
+    let mut view = MensaView {
+        days: Cursor::from(&weekdays),
+        week: current_week(),
+        place,
+        filter,
+        tier,
+        all_prices: false,
+        week_view: false,
+        rated: None,
+    };
+    // the plan of the weekend is useless, show the next week instead
+    let today = chrono::Utc::now().with_timezone(&Berlin).weekday();
+    match weekdays.iter().position(|day| *day == Weekday::from(today)) {
+        Some(index) if today.number_from_monday() <= 5 => {
+            view.days.select(index);
+        }
+        _ => view.week += 1,
+    }
+    let ratings = get_dish_ratings(&ctx.data().database, &place).await?;
+    let embed = view.embed(&mensa_today, &ratings);
+    let has_next_week = !plan_week(mensa_today.plan(), current_week() + 1).is_empty();
 
     let weekday_control = menu::Control::new(
         menu::MenuComponent::select("weekday", |button| {
-            button.options(|opts| opts.set_options(create_day_options(Some(weekday_today.into()))))
+            button.options(|opts| opts.set_options(create_day_options(Some(view.day()))))
         }),
         Arc::new(|menu, mci| Box::pin(select_weekday(menu, mci))),
     );
-
     let mensa_control = menu::Control::new(
         menu::MenuComponent::select("mensa", |button| {
-            button.options(|opts| opts.set_options(create_mensa_options(Some(DEFAULT_PLACE))))
+            button.options(|opts| opts.set_options(create_mensa_options(Some(place))))
         }),
         Arc::new(|menu, mci| Box::pin(select_mensa(menu, mci))),
     );
-
-    let mut menu = Menu::new(
-        &ctx,
-        (Some(chrono::Utc::now().weekday().into()), Some(DEFAULT_PLACE), mensa_cache),
-        |options| {
-            options
-                .add_row(|row| row.add_button(weekday_control))
-                .add_row(|row| row.add_button(mensa_control))
-                .set_timeout(3600)
-        },
+    let prev_control = menu::Control::new(
+        create_prev_day_button(),
+        Arc::new(|menu, mci| Box::pin(select_prev_day(menu, mci))),
+    );
+    let next_control = menu::Control::new(
+        create_next_day_button(),
+        Arc::new(|menu, mci| Box::pin(select_next_day(menu, mci))),
+    );
+    let week_control = menu::Control::new(
+        create_week_button(&view),
+        Arc::new(|menu, mci| Box::pin(toggle_week_view(menu, mci))),
+    );
+    let next_week_control = menu::Control::new(
+        create_next_week_button(&view, has_next_week),
+        Arc::new(|menu, mci| Box::pin(toggle_next_week(menu, mci))),
     );
+    let prices_control = menu::Control::new(
+        create_prices_button(false),
+        Arc::new(|menu, mci| Box::pin(toggle_prices(menu, mci))),
+    );
+    let rate_dish_control = menu::Control::new(
+        create_rate_dish_select(&view, &mensa_today),
+        Arc::new(|menu, mci| Box::pin(select_rated_dish(menu, mci))),
+    );
+    let rate_controls = (1..=5)
+        .map(|stars| {
+            menu::Control::new(
+                create_rate_button(stars, false),
+                Arc::new(move |menu, mci| Box::pin(rate(menu, mci, stars))),
+            )
+        })
+        .collect::<Vec<menu::Control<MensaView<'_>>>>();
+
+    let mut menu = Menu::new(&ctx, view, |options| {
+        options
+            .add_row(|row| row.add_button(weekday_control))
+            .add_row(|row| row.add_button(mensa_control))
+            .add_row(|row| {
+                row.add_button(prev_control)
+                    .add_button(next_control)
+                    .add_button(week_control)
+                    .add_button(next_week_control);
+                // there is nothing to toggle without a price tier
+                if tier.is_some() {
+                    row.add_button(prices_control);
+                }
+                row
+            })
+            .add_row(|row| row.add_button(rate_dish_control))
+            .add_row(|row| {
+                for control in rate_controls {
+                    row.add_button(control);
+                }
+                row
+            })
+            .set_timeout(3600)
+    });
 
     menu.run(|m| {
         m.embed(|e| {
@@ -73,29 +600,96 @@ pub async fn mensa(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-#[derive(Default)]
-pub struct MensaCache {
-    mensa_token: String,
-    cache_map: HashMap<MensaPlace, Plan>,
+fn create_prev_day_button() -> menu::MenuComponent {
+    menu::MenuComponent::button("prev_day", |button| {
+        button.style(serenity::ButtonStyle::Primary).label("<")
+    })
 }
 
-impl<'a> MensaCache {
-    pub fn new(mensa_token: &str) -> Self {
-        MensaCache {
-            mensa_token: mensa_token.to_string(),
-            ..Default::default()
+fn create_next_day_button() -> menu::MenuComponent {
+    menu::MenuComponent::button("next_day", |button| {
+        button.style(serenity::ButtonStyle::Primary).label(">")
+    })
+}
+
+fn create_week_button(view: &MensaView<'_>) -> menu::MenuComponent {
+    let week_view = view.week_view;
+    menu::MenuComponent::button("week", |button| {
+        button
+            .style(serenity::ButtonStyle::Secondary)
+            .label(if week_view { "day" } else { "week" })
+    })
+}
+
+fn create_next_week_button(view: &MensaView<'_>, has_next_week: bool) -> menu::MenuComponent {
+    let next_week = view.week > current_week();
+    menu::MenuComponent::button("next_week", |button| {
+        button
+            .style(serenity::ButtonStyle::Secondary)
+            .label(if next_week { "this week" } else { "next week" })
+            // the swfr doesn't always have the plan of the next week yet
+            .disabled(!next_week && !has_next_week)
+    })
+}
+
+fn create_prices_button(all_prices: bool) -> menu::MenuComponent {
+    menu::MenuComponent::button("prices", |button| {
+        button
+            .style(serenity::ButtonStyle::Secondary)
+            .label(if all_prices { "my price" } else { "all prices" })
+    })
+}
+
+/// the dishes of the shown day, nothing can be rated in the week view or in the future
+fn create_rate_dish_select(view: &MensaView<'_>, mensa: &MensaPlan) -> menu::MenuComponent {
+    let mut options = vec![];
+    if view.rated_date().is_some() {
+        for (index, menu) in plan_day(mensa.plan(), view.day(), view.week)
+            .into_iter()
+            .flat_map(|day| day.menues.iter().enumerate())
+        {
+            let dish = Dish::parse(&menu.name, menu.food_type.as_deref());
+            if !view.filter.allows(&dish) {
+                continue;
+            }
+            // labels of select options can't be longer than 100 characters
+            let label = format!("{}: {}", menu.art, dish.components.join(", "))
+                .chars()
+                .take(100)
+                .collect::<String>();
+            let mut option = serenity::CreateSelectMenuOption::new(label, index);
+            if matches!(&view.rated, Some((rated, _)) if rated.key() == dish.key()) {
+                option.default_selection(true);
+            }
+            options.push(option);
         }
     }
-
-    pub async fn mensa_plan(&'a mut self, place: &MensaPlace) -> Result<&'a Plan, Error> {
-        let mut url_builder = UrlBuilder::new(&self.mensa_token);
-        let future = mensa_fr::request(url_builder.set_place(&place));
-        Ok(self.cache_map.entry(*place).or_insert(future.await?))
+    let disabled = options.is_empty();
+    if disabled {
+        options.push(serenity::CreateSelectMenuOption::new(
+            "nothing to rate",
+            "none",
+        ));
     }
+    menu::MenuComponent::select("rate_dish", |select| {
+        select
+            .placeholder("rate a dish")
+            .disabled(disabled)
+            .options(|opts| opts.set_options(options))
+    })
+}
+
+fn create_rate_button(stars: u8, enabled: bool) -> menu::MenuComponent {
+    menu::MenuComponent::button(&format!("rate_{}", stars), |button| {
+        button
+            .style(serenity::ButtonStyle::Secondary)
+            .label(format!("{}★", stars))
+            .disabled(!enabled)
+    })
 }
 
 async fn select_weekday(
-    menu: &mut Menu<'_, (Option<Weekday>, Option<MensaPlace>, MensaCache)>,
+    menu: &mut Menu<'_, MensaView<'_>>,
     mci: &Arc<serenity::MessageComponentInteraction>,
 ) -> Result<(), Error> {
     let dds = &mci.data.values;
@@ -103,76 +697,219 @@ async fn select_weekday(
         .get(0)
         .map(|day| Weekday::try_from(day.as_str()))
         .unwrap_or(Ok(chrono::Utc::now().weekday().into()))?;
-    menu.data.0 = Some(weekday);
+    menu.data.show_day(weekday);
     update_message(menu, mci).await?;
     Ok(())
 }
 
 async fn select_mensa(
-    menu: &mut Menu<'_, (Option<Weekday>, Option<MensaPlace>, MensaCache)>,
+    menu: &mut Menu<'_, MensaView<'_>>,
     mci: &Arc<serenity::MessageComponentInteraction>,
 ) -> Result<(), Error> {
     let dds = &mci.data.values;
     let place = dds
         .get(0)
         .map(|place| MensaPlace::try_from(place.as_str()))
-        .unwrap_or(Ok(DEFAULT_PLACE))?;
-    menu.data.1 = Some(place);
+        .unwrap_or(Ok(menu.data.place))?;
+    menu.data.show_place(place);
+    update_message(menu, mci).await?;
+    Ok(())
+}
+
+/// the function to show the previous day, the last day of the previous week on the first day
+async fn select_prev_day(
+    menu: &mut Menu<'_, MensaView<'_>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<(), Error> {
+    let view = &mut menu.data;
+    if view.days.index() == 0 {
+        view.week -= 1;
+    }
+    view.days.prev();
+    view.week_view = false;
+    menu.data.rated = None;
+    update_message(menu, mci).await?;
+    Ok(())
+}
+
+/// the function to show the next day, the first day of the next week on the last day
+async fn select_next_day(
+    menu: &mut Menu<'_, MensaView<'_>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<(), Error> {
+    let view = &mut menu.data;
+    view.days.next();
+    if view.days.index() == 0 {
+        view.week += 1;
+    }
+    view.week_view = false;
+    menu.data.rated = None;
+    update_message(menu, mci).await?;
+    Ok(())
+}
+
+async fn toggle_week_view(
+    menu: &mut Menu<'_, MensaView<'_>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<(), Error> {
+    menu.data.week_view = !menu.data.week_view;
+    menu.data.rated = None;
+    update_message(menu, mci).await?;
+    Ok(())
+}
+
+async fn toggle_next_week(
+    menu: &mut Menu<'_, MensaView<'_>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<(), Error> {
+    let week = current_week();
+    menu.data.week = if menu.data.week > week {
+        week
+    } else {
+        week + 1
+    };
+    menu.data.rated = None;
+    update_message(menu, mci).await?;
+    Ok(())
+}
+
+async fn toggle_prices(
+    menu: &mut Menu<'_, MensaView<'_>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<(), Error> {
+    menu.data.all_prices = !menu.data.all_prices;
+    update_message(menu, mci).await?;
+    Ok(())
+}
+
+/// the function to choose the dish that is rated with the rating buttons
+///
+/// a dish that can't be rated is answered only to the user, an error would end the menu
+async fn select_rated_dish(
+    menu: &mut Menu<'_, MensaView<'_>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+) -> Result<(), Error> {
+    let data = menu.ctx.data();
+    let mensa = data
+        .mensa_cache
+        .mensa_plan(&data.database, &menu.data.place)
+        .await?;
+    match menu.data.rated_dish(mensa.plan(), mci.data.values.get(0)) {
+        Ok(rated) => menu.data.rated = Some(rated),
+        Err(why) => return reply_rating_refused(menu, mci, why).await,
+    }
+    update_message(menu, mci).await?;
+    Ok(())
+}
+
+async fn reply_rating_refused(
+    menu: &Menu<'_, MensaView<'_>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+    message: &str,
+) -> Result<(), Error> {
+    mci.create_interaction_response(&menu.ctx.serenity_context(), |ir| {
+        ir.kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+            .interaction_response_data(|m| m.content(message).ephemeral(true))
+    })
+    .await?;
+    Ok(())
+}
+
+/// the function to rate the chosen dish with the stars of the button
+async fn rate(
+    menu: &mut Menu<'_, MensaView<'_>>,
+    mci: &Arc<serenity::MessageComponentInteraction>,
+    stars: u8,
+) -> Result<(), Error> {
+    let (dish, served_on) = menu
+        .data
+        .rated
+        .take()
+        .ok_or(AYError::InvalidInput("choose a dish to rate first"))?;
+    rate_dish(
+        &menu.ctx.data().database,
+        mci.user.id.0 as i64,
+        &menu.data.place,
+        &dish,
+        &served_on,
+        stars,
+    )
+    .await?;
     update_message(menu, mci).await?;
     Ok(())
 }
 
 async fn update_message(
-    menu: &mut Menu<'_, (Option<Weekday>, Option<MensaPlace>, MensaCache)>,
+    menu: &mut Menu<'_, MensaView<'_>>,
     mci: &Arc<serenity::MessageComponentInteraction>,
 ) -> Result<(), Error> {
-    let (day, place, mensa_cache) = &mut menu.data;
+    let view = &menu.data;
     let mut mes = mci.message.clone();
-    if let (Some(day), Some(place)) = (&day, &place) {
-        let mensa = mensa_cache.mensa_plan(place).await?;
-        let embed = mensa
-            .day(*day)
-            .map(|day| create_mensa_plan_by_day(day))
-            .unwrap_or({
-                let mut embed = serenity::CreateEmbed::default();
-                embed.description("no mensa today").color(crate::color());
-                embed
-            });
-        mes.edit(&menu.ctx.serenity_context(), |edit| {
-            edit.set_embed(embed)
-                .set_components({
-                    let mut comp = serenity::CreateComponents::default();
-                    comp.add_action_row({
-                        let mut row = serenity::CreateActionRow::default();
-                        set_button(
-                            &mut row,
-                            &menu::MenuComponent::select("weekday", |button| {
-                                button.options(|opts| {
-                                    opts.set_options(create_day_options(Some(*day)))
-                                })
-                            }),
-                        );
-                        row
-                    })
-                    .add_action_row({
-                        let mut row = serenity::CreateActionRow::default();
-                        set_button(
-                            &mut row,
-                            &menu::MenuComponent::select("mensa", |button| {
-                                button.options(|opts| {
-                                    opts.set_options(create_mensa_options(Some(*place)))
-                                })
-                            }),
-                        );
-                        row
-                    });
-
-                    comp
-                })
-                .content("")
-        })
+    let data = menu.ctx.data();
+    let mensa = data
+        .mensa_cache
+        .mensa_plan(&data.database, &view.place)
         .await?;
-    }
+    let ratings = get_dish_ratings(&data.database, &view.place).await?;
+    let embed = view.embed(&mensa, &ratings);
+    let has_next_week = !plan_week(mensa.plan(), current_week() + 1).is_empty();
+    mes.edit(&menu.ctx.serenity_context(), |edit| {
+        edit.set_embed(embed)
+            .set_components({
+                let mut comp = serenity::CreateComponents::default();
+                comp.add_action_row({
+                    let mut row = serenity::CreateActionRow::default();
+                    set_button(
+                        &mut row,
+                        &menu::MenuComponent::select("weekday", |button| {
+                            button.options(|opts| {
+                                opts.set_options(create_day_options(Some(view.day())))
+                            })
+                        }),
+                    );
+                    row
+                })
+                .add_action_row({
+                    let mut row = serenity::CreateActionRow::default();
+                    set_button(
+                        &mut row,
+                        &menu::MenuComponent::select("mensa", |button| {
+                            button.options(|opts| {
+                                opts.set_options(create_mensa_options(Some(view.place)))
+                            })
+                        }),
+                    );
+                    row
+                })
+                .add_action_row({
+                    let mut row = serenity::CreateActionRow::default();
+                    set_button(&mut row, &create_prev_day_button());
+                    set_button(&mut row, &create_next_day_button());
+                    set_button(&mut row, &create_week_button(view));
+                    set_button(&mut row, &create_next_week_button(view, has_next_week));
+                    if view.tier.is_some() {
+                        set_button(&mut row, &create_prices_button(view.all_prices));
+                    }
+                    row
+                })
+                .add_action_row({
+                    let mut row = serenity::CreateActionRow::default();
+                    set_button(&mut row, &create_rate_dish_select(view, &mensa));
+                    row
+                })
+                .add_action_row({
+                    let mut row = serenity::CreateActionRow::default();
+                    for stars in 1..=5 {
+                        set_button(&mut row, &create_rate_button(stars, view.rated.is_some()));
+                    }
+                    row
+                });
+
+                comp
+            })
+            .content("")
+    })
+    .await?;
     Ok(())
 }
 
@@ -215,3 +952,130 @@ fn create_day_options(selected: Option<Weekday>) -> Vec<serenity::CreateSelectMe
         |day| Box::new(*day),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mensa::{week_of_day, FixtureSource, PlanSource, DEFAULT_FIXTURES};
+
+    /// the recorded plans of fixtures/mensa, rempartstraße as xml and institutsviertel as json
+    async fn recorded_plan(place: MensaPlace) -> Plan {
+        FixtureSource::new(DEFAULT_FIXTURES)
+            .request(&place)
+            .await
+            .unwrap()
+    }
+
+    /// the view of the recorded week on `weekday`
+    fn recorded_view<'a>(
+        weekdays: &'a Vec<Weekday>,
+        plan: &Plan,
+        place: MensaPlace,
+        weekday: chrono::Weekday,
+    ) -> MensaView<'a> {
+        let monday = plan.day(Weekday::from(chrono::Weekday::Mon)).unwrap();
+        let mut view = MensaView {
+            days: Cursor::from(weekdays),
+            week: week_of_day(monday),
+            place,
+            filter: MenuFilter::default(),
+            tier: None,
+            all_prices: false,
+            week_view: false,
+            rated: None,
+        };
+        view.show_day(Weekday::from(weekday));
+        view
+    }
+
+    fn value(text: &str) -> Option<String> {
+        Some(String::from(text))
+    }
+
+    #[tokio::test]
+    async fn selects_a_served_dish_of_a_recorded_plan() {
+        let plan = recorded_plan(MensaPlace::Rempartstraße).await;
+        let weekdays = Weekday::iter().collect::<Vec<Weekday>>();
+        let view = recorded_view(
+            &weekdays,
+            &plan,
+            MensaPlace::Rempartstraße,
+            chrono::Weekday::Tue,
+        );
+        let (dish, served_on) = view.rated_dish(&plan, value("1").as_ref()).unwrap();
+        assert_eq!(dish.components, vec!["Seelachsfilet", "Kartoffelsalat"]);
+        assert_eq!(served_on, "2023-06-06");
+    }
+
+    #[tokio::test]
+    async fn refuses_dishes_that_are_not_on_the_plan() {
+        let plan = recorded_plan(MensaPlace::Rempartstraße).await;
+        let weekdays = Weekday::iter().collect::<Vec<Weekday>>();
+        let mut view = recorded_view(
+            &weekdays,
+            &plan,
+            MensaPlace::Rempartstraße,
+            chrono::Weekday::Mon,
+        );
+        // the select sends "none" if there is nothing to rate
+        for selected in [None, value("none"), value("3")] {
+            assert!(view.rated_dish(&plan, selected.as_ref()).is_err());
+        }
+        // the recorded plan isn't the plan of another week
+        view.week += 1;
+        assert!(view.rated_dish(&plan, value("0").as_ref()).is_err());
+    }
+
+    #[tokio::test]
+    async fn nothing_is_rated_on_a_closed_day_of_a_json_plan() {
+        let plan = recorded_plan(MensaPlace::Institutsviertel).await;
+        let weekdays = Weekday::iter().collect::<Vec<Weekday>>();
+        let mut view = recorded_view(
+            &weekdays,
+            &plan,
+            MensaPlace::Institutsviertel,
+            chrono::Weekday::Mon,
+        );
+        assert!(view.rated_dish(&plan, value("1").as_ref()).is_ok());
+        view.show_day(Weekday::from(chrono::Weekday::Tue));
+        assert!(view.rated_dish(&plan, value("0").as_ref()).is_err());
+    }
+
+    #[tokio::test]
+    async fn changing_the_day_or_mensa_drops_the_rated_dish() {
+        let plan = recorded_plan(MensaPlace::Rempartstraße).await;
+        let weekdays = Weekday::iter().collect::<Vec<Weekday>>();
+        let mut view = recorded_view(
+            &weekdays,
+            &plan,
+            MensaPlace::Rempartstraße,
+            chrono::Weekday::Mon,
+        );
+        view.rated = Some(view.rated_dish(&plan, value("0").as_ref()).unwrap());
+        view.week_view = true;
+        view.show_day(Weekday::from(chrono::Weekday::Tue));
+        assert!(view.rated.is_none());
+        assert!(!view.week_view);
+        assert_eq!(view.days.index(), 1);
+
+        view.rated = Some(view.rated_dish(&plan, value("0").as_ref()).unwrap());
+        view.show_place(MensaPlace::Institutsviertel);
+        assert!(view.rated.is_none());
+    }
+
+    #[test]
+    fn dishes_of_the_next_week_cannot_be_rated() {
+        let weekdays = Weekday::iter().collect::<Vec<Weekday>>();
+        let view = MensaView {
+            days: Cursor::from(&weekdays),
+            week: current_week() + 1,
+            place: DEFAULT_PLACE,
+            filter: MenuFilter::default(),
+            tier: None,
+            all_prices: false,
+            week_view: false,
+            rated: None,
+        };
+        assert_eq!(view.rated_date(), None);
+    }
+}
@@ -1,6 +1,7 @@
 use poise::async_trait;
 use std::error::Error as StdError;
 use std::fmt;
+use std::time::Duration;
 use tracing::instrument;
 
 use crate::Context;
@@ -18,14 +19,21 @@ where
 #[non_exhaustive]
 pub enum Error {
     InvalidInput(&'static str),
-    Unavailable(&'static str),
+    /// something can't be used right now, maybe only until `retry_after`
+    Unavailable(&'static str, Option<Duration>),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::InvalidInput(msg) => f.write_str(msg),
-            Self::Unavailable(msg) => f.write_str(msg),
+            Self::Unavailable(msg, None) => f.write_str(msg),
+            Self::Unavailable(msg, Some(retry_after)) => write!(
+                f,
+                "{}, try again in {} seconds",
+                msg,
+                retry_after.as_secs_f64().ceil() as u64
+            ),
         }
     }
 }
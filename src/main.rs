@@ -13,9 +13,12 @@ use std::sync::{
 };
 use tracing_subscriber::fmt::time::UtcTime;
 
+pub mod apex;
 pub mod commands;
 pub mod error;
+pub mod mensa;
 pub mod menu;
+pub mod overwrites;
 pub mod penis;
 pub mod util;
 
@@ -39,6 +42,12 @@ pub struct Config {
     swfr_token: Option<String>,
     prefix: Option<String>,
     color: Option<String>,
+    sync_exclusions: Option<bool>,
+    mensa_cache_ttl: Option<i64>,
+    mensa_hours: Option<String>,
+    mensa_fixtures: Option<String>,
+    apex_cache_ttl: Option<i64>,
+    apex_rate_limit: Option<f64>,
 }
 
 // some global stuff like configuration etc.
@@ -74,13 +83,31 @@ lazy_static! {
             None => None
         }
     };
+
+    // the endpoints apex-rs doesn't have, with the same token but a separate http client
+    static ref APEX_API: Option<apex::ApexApi> = {
+        CONFIG.apex_token.as_deref().map(apex::ApexApi::new)
+    };
 }
 
 // kinda like a singleton, because I wanted to remove the unwrap
 pub fn apex_client<'a>() -> Result<&'a apex_rs::ApexClient<'static>> {
     match &*APEX_CLIENT {
         Some(client) => Ok(client),
-        None => Err(Box::new(AYError::Unavailable("apex token not in config"))),
+        None => Err(Box::new(AYError::Unavailable(
+            "apex token not in config",
+            None,
+        ))),
+    }
+}
+
+pub fn apex_api<'a>() -> Result<&'a apex::ApexApi> {
+    match &*APEX_API {
+        Some(api) => Ok(api),
+        None => Err(Box::new(AYError::Unavailable(
+            "apex token not in config",
+            None,
+        ))),
     }
 }
 
@@ -101,6 +128,9 @@ type Result<T> = StdResult<T, Error>;
 #[non_exhaustive]
 pub struct Data {
     pub database: sqlx::SqlitePool,
+    pub mensa_cache: Arc<mensa::MensaCache>,
+    pub apex_cache: Arc<apex::ApexCache>,
+    pub text_policies: Arc<util::GuildPolicies>,
 }
 
 /// custom event listener
@@ -115,7 +145,59 @@ async fn event_listener(
             tracing::info!("{} is connected!", data_about_bot.user.name);
             tracing::info!("Total Guilds: {}", data_about_bot.guilds.len())
         }
-        poise::Event::VoiceStateUpdate { old, new } => {}
+        // members of the channels are only known once the guilds are cached
+        poise::Event::CacheReady { guilds } => {
+            if CONFIG.sync_exclusions.unwrap_or(false) {
+                for guild_id in guilds {
+                    if let Err(why) = util::sync_guild_exclusions(ctx, data, *guild_id).await {
+                        tracing::error!("couldn't sync exclusions of {}: {}", guild_id, why);
+                    }
+                }
+            }
+        }
+        poise::Event::VoiceStateUpdate { old, new } => {
+            // only check if the user actually moved to another channel
+            if old.as_ref().and_then(|old| old.channel_id) != new.channel_id {
+                util::check_voice_state_for_exclusion(ctx, new, data).await?
+            }
+        }
+        poise::Event::GuildMemberAddition { new_member } => {
+            util::check_guild_for_exclusion_collision(ctx, new_member.guild_id, data).await?
+        }
+        // roles of the member might be part of an exclusion group
+        poise::Event::GuildMemberUpdate {
+            old_if_available,
+            new,
+        } => {
+            // without the old member every role of the member might be new
+            let changed = match old_if_available {
+                Some(old) => old
+                    .roles
+                    .iter()
+                    .filter(|role| !new.roles.contains(role))
+                    .chain(new.roles.iter().filter(|role| !old.roles.contains(role)))
+                    .copied()
+                    .collect(),
+                None => new.roles.clone(),
+            };
+            if !changed.is_empty()
+                && util::is_exclusion_group_role(&data.database, new.guild_id.into(), &changed)
+                    .await?
+            {
+                util::check_guild_for_exclusion_collision(ctx, new.guild_id, data).await?
+            }
+        }
+        poise::Event::Message { new_message } => {
+            util::check_message_for_exclusion(ctx, new_message, data).await?
+        }
+        poise::Event::ChannelCreate { channel } => {
+            util::check_for_exclusion_collision(
+                ctx,
+                serenity::Channel::Guild(channel.clone()),
+                data,
+            )
+            .await?
+        }
         poise::Event::ChannelUpdate { old: _, new } => {
             util::check_for_exclusion_collision(ctx, new.clone(), data).await?
         }
@@ -131,7 +213,11 @@ async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
     // and forward the rest to the default handler
     match error {
         poise::FrameworkError::Setup { error, .. } => panic!("Failed to start bot: {:?}", error),
-        poise::FrameworkError::Command { error, ctx } => {
+        poise::FrameworkError::Command { error, ctx }
+        | poise::FrameworkError::CommandCheckFailed {
+            error: Some(error),
+            ctx,
+        } => {
             if let Some(ayerr) = error.downcast_ref::<AYError>() {
                 // notify user
                 if let Err(e) = ayerr.send(&ctx).await {
@@ -192,6 +278,7 @@ async fn run_discord(config: &Config, database: SqlitePool) -> Result<()> {
             invite(),
             shutdown(),
             maps(),
+            apex(),
             mensa(),
         ],
         prefix_options: poise::PrefixFrameworkOptions {
@@ -213,15 +300,74 @@ async fn run_discord(config: &Config, database: SqlitePool) -> Result<()> {
 
     Ok(poise::Framework::builder()
         .token(config.discord_token.to_string())
-        .setup(move |_ctx, _ready, framework| {
+        .setup(move |ctx, _ready, framework| {
             // we register signal handlers for sigterm, ctrl+c, ...
             register_signal_handler(framework.shard_manager().clone());
+            // read by every guild message, so it's kept in memory
+            let text_policies = Arc::new(util::GuildPolicies::default());
+            // lift exclusions once they expire
+            tokio::spawn(util::lift_expired_exclusions(
+                ctx.clone(),
+                database.clone(),
+                text_policies.clone(),
+            ));
+            let plan_source: Box<dyn mensa::PlanSource> = match CONFIG
+                .swfr_token
+                .as_deref()
+                .filter(|token| !token.is_empty())
+            {
+                Some(token) => Box::new(mensa::SwfrSource::new(token.to_string())),
+                // without a token the bot runs against recorded plans
+                None => {
+                    let fixtures = CONFIG
+                        .mensa_fixtures
+                        .as_deref()
+                        .unwrap_or(mensa::DEFAULT_FIXTURES);
+                    tracing::warn!(
+                        "no swfr token in config, using the mensa plans in {}",
+                        fixtures
+                    );
+                    Box::new(mensa::FixtureSource::new(fixtures))
+                }
+            };
+            // plans are shared by all invocations of the mensa commands
+            let mensa_cache = Arc::new(mensa::MensaCache::new(
+                plan_source,
+                CONFIG.mensa_cache_ttl.unwrap_or(mensa::DEFAULT_TTL_MINUTES),
+            ));
+            // responses of the apex api are shared to stay below the rate limit
+            let apex_cache = Arc::new(apex::ApexCache::new(
+                CONFIG.apex_cache_ttl.unwrap_or(apex::DEFAULT_TTL_SECONDS),
+                CONFIG.apex_rate_limit.unwrap_or(apex::DEFAULT_RATE_LIMIT),
+            ));
+            // post the new maps to the subscribed channels once a rotation ends
+            tokio::spawn(apex::post_map_changes(
+                ctx.clone(),
+                database.clone(),
+                apex_cache.clone(),
+            ));
+            // post the mensa plans to the subscribed channels
+            tokio::spawn(mensa::post_subscribed_mensa_plans(
+                ctx.clone(),
+                database.clone(),
+                mensa_cache.clone(),
+            ));
             // create user data
-            Box::pin(async move { Ok(Data { database }) })
+            Box::pin(async move {
+                Ok(Data {
+                    database,
+                    mensa_cache,
+                    apex_cache,
+                    text_policies,
+                })
+            })
         })
         .options(options)
         .intents(
-            serenity::GatewayIntents::non_privileged() | serenity::GatewayIntents::MESSAGE_CONTENT,
+            serenity::GatewayIntents::non_privileged()
+                | serenity::GatewayIntents::MESSAGE_CONTENT
+                // needed for member joins and the member cache used by exclusions
+                | serenity::GatewayIntents::GUILD_MEMBERS,
         )
         .run()
         .await?)
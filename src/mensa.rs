@@ -0,0 +1,1854 @@
+use std::{collections::HashMap, future::Future, path::PathBuf, pin::Pin, sync::Arc};
+
+use crate::{
+    error::Error as AYError,
+    util::{from_timestamp, to_relative_timestamp},
+    Result,
+};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc, Weekday};
+use chrono_tz::Europe::Berlin;
+use figment::{
+    providers::{Format, Toml},
+    Figment,
+};
+use lazy_static::lazy_static;
+use mensa_fr::{
+    mensa::{self, Day, Plan},
+    MensaPlace, UrlBuilder,
+};
+use mensa_swfr_rs as mensa_fr;
+use poise::serenity_prelude as serenity;
+use regex::Regex;
+use serde::Deserialize;
+use strum::IntoEnumIterator;
+use tokio::sync::{Mutex, RwLock};
+
+/// how long a plan is used before it's requested again, if not configured
+pub const DEFAULT_TTL_MINUTES: i64 = 60;
+/// how long the swfr isn't asked again for a plan after a request failed
+const RETRY_MINUTES: i64 = 5;
+/// where the recorded plans are read from without a swfr token, if not configured
+pub const DEFAULT_FIXTURES: &str = "fixtures/mensa";
+/// the mensa if neither the user nor the guild chose one
+pub const DEFAULT_PLACE: MensaPlace = MensaPlace::Rempartstraße;
+
+/// a mensa plan and when it was requested from the swfr
+#[derive(Clone)]
+pub struct MensaPlan {
+    plan: Arc<Plan>,
+    fetched_at: DateTime<Utc>,
+    stale: bool,
+}
+
+impl MensaPlan {
+    pub fn plan(&self) -> &Plan {
+        &self.plan
+    }
+
+    pub fn fetched_at(&self) -> &DateTime<Utc> {
+        &self.fetched_at
+    }
+
+    /// when the plan was requested, if the swfr couldn't be reached for a newer one
+    pub fn stale_since(&self) -> Option<&DateTime<Utc>> {
+        self.stale.then_some(&self.fetched_at)
+    }
+}
+
+pub type PlanRequest<'a> = Pin<Box<dyn Future<Output = Result<Plan>> + Send + 'a>>;
+
+/// where the mensa plans come from
+pub trait PlanSource: Send + Sync {
+    fn request<'a>(&'a self, place: &'a MensaPlace) -> PlanRequest<'a>;
+}
+
+/// requests the plans from the swfr api
+pub struct SwfrSource {
+    token: String,
+}
+
+impl SwfrSource {
+    pub fn new(token: String) -> Self {
+        SwfrSource { token }
+    }
+}
+
+impl PlanSource for SwfrSource {
+    fn request<'a>(&'a self, place: &'a MensaPlace) -> PlanRequest<'a> {
+        Box::pin(async move {
+            let mut url_builder = UrlBuilder::new(&self.token);
+            Ok(mensa_fr::request(url_builder.set_place(place)).await?)
+        })
+    }
+}
+
+/// reads recorded plans from `<directory>/<place id>.json` or `<directory>/<place id>.xml`
+///
+/// json plans are stored like in the `mensa_plans` table, xml plans like the swfr sends them
+pub struct FixtureSource {
+    directory: PathBuf,
+}
+
+impl FixtureSource {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        FixtureSource {
+            directory: directory.into(),
+        }
+    }
+}
+
+impl PlanSource for FixtureSource {
+    fn request<'a>(&'a self, place: &'a MensaPlace) -> PlanRequest<'a> {
+        Box::pin(async move {
+            let path = self.directory.join(format!("{}.json", place.id()));
+            if let Ok(content) = tokio::fs::read_to_string(&path).await {
+                return Ok(serde_json::from_str::<Plan>(&content)?);
+            }
+            let path = self.directory.join(format!("{}.xml", place.id()));
+            match tokio::fs::read_to_string(&path).await {
+                Ok(content) => Ok(serde_xml_rs::from_str::<Plan>(&content)?),
+                Err(_) => Err(Box::new(AYError::Unavailable(
+                    "there is no recorded plan of this mensa",
+                    None,
+                ))),
+            }
+        })
+    }
+}
+
+/// process-wide cache of the mensa plans, written through to the database
+pub struct MensaCache {
+    source: Box<dyn PlanSource>,
+    ttl: Duration,
+    plans: RwLock<HashMap<MensaPlace, MensaPlan>>,
+    /// one lock per place, so only one request for a plan is running at a time
+    requests: Mutex<HashMap<MensaPlace, Arc<Mutex<()>>>>,
+    /// when the last request for the plan of a place failed
+    failures: RwLock<HashMap<MensaPlace, DateTime<Utc>>>,
+}
+
+impl MensaCache {
+    pub fn new(source: Box<dyn PlanSource>, ttl_minutes: i64) -> Self {
+        MensaCache {
+            source,
+            ttl: Duration::minutes(ttl_minutes),
+            plans: RwLock::new(HashMap::new()),
+            requests: Mutex::new(HashMap::new()),
+            failures: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// the plan of `place`, requested again once the ttl is over
+    ///
+    /// falls back to the last known plan if the swfr can't be reached
+    pub async fn mensa_plan(
+        &self,
+        database: &sqlx::SqlitePool,
+        place: &MensaPlace,
+    ) -> Result<MensaPlan> {
+        let cached = self.cached_plan(database, place).await?;
+        if let Some(cached) = &cached {
+            if Utc::now() - cached.fetched_at < self.ttl {
+                return Ok(cached.clone());
+            }
+        }
+
+        let lock = self
+            .requests
+            .lock()
+            .await
+            .entry(*place)
+            .or_default()
+            .clone();
+        let plan = {
+            let _request = lock.lock().await;
+            self.request(database, place).await
+        };
+        // nobody else waits for the place if only the map and this request hold the lock
+        let mut requests = self.requests.lock().await;
+        if Arc::strong_count(&lock) == 2 {
+            requests.remove(place);
+        }
+        plan
+    }
+
+    /// the plan in memory, or the stored one from before a restart
+    async fn cached_plan(
+        &self,
+        database: &sqlx::SqlitePool,
+        place: &MensaPlace,
+    ) -> Result<Option<MensaPlan>> {
+        if let Some(plan) = self.plans.read().await.get(place) {
+            return Ok(Some(plan.clone()));
+        }
+        let plan = load_mensa_plan(database, place).await?;
+        if let Some(plan) = &plan {
+            self.plans.write().await.insert(*place, plan.clone());
+        }
+        Ok(plan)
+    }
+
+    /// requests the plan while holding the lock of the place
+    async fn request(&self, database: &sqlx::SqlitePool, place: &MensaPlace) -> Result<MensaPlan> {
+        // the request that held the lock before might have answered already
+        let cached = self.cached_plan(database, place).await?;
+        if let Some(cached) = &cached {
+            if Utc::now() - cached.fetched_at < self.ttl {
+                return Ok(cached.clone());
+            }
+        }
+        // the swfr isn't asked again right after it failed
+        let retry_in = self
+            .failures
+            .read()
+            .await
+            .get(place)
+            .map(|failed_at| *failed_at + Duration::minutes(RETRY_MINUTES) - Utc::now())
+            .filter(|retry_in| *retry_in > Duration::zero());
+        let response = match retry_in {
+            Some(retry_in) => Err(AYError::Unavailable(
+                "the swfr couldn't be reached",
+                retry_in.to_std().ok(),
+            )
+            .into()),
+            None => self.source.request(place).await,
+        };
+
+        match response {
+            Ok(plan) => {
+                let plan = MensaPlan {
+                    plan: Arc::new(plan),
+                    fetched_at: Utc::now(),
+                    stale: false,
+                };
+                if let Err(why) = store_mensa_plan(database, place, &plan).await {
+                    tracing::error!("couldn't store mensa plan of {}: {}", place, why);
+                }
+                self.plans.write().await.insert(*place, plan.clone());
+                self.failures.write().await.remove(place);
+                Ok(plan)
+            }
+            Err(why) => {
+                // only the request itself is a new failure, not the back-off
+                if retry_in.is_none() {
+                    tracing::warn!("couldn't request mensa plan of {}: {}", place, why);
+                    self.failures.write().await.insert(*place, Utc::now());
+                }
+                match cached {
+                    Some(cached) => Ok(MensaPlan {
+                        stale: true,
+                        ..cached
+                    }),
+                    None => Err(why),
+                }
+            }
+        }
+    }
+}
+
+async fn load_mensa_plan(
+    database: &sqlx::SqlitePool,
+    place: &MensaPlace,
+) -> Result<Option<MensaPlan>> {
+    let place_id = place.id().to_string();
+    let record = sqlx::query!(
+        "SELECT plan, fetched_at FROM mensa_plans WHERE place = ?",
+        place_id
+    )
+    .fetch_optional(database)
+    .await?;
+    Ok(match record {
+        Some(record) => match serde_json::from_str::<Plan>(&record.plan) {
+            Ok(plan) => Some(MensaPlan {
+                plan: Arc::new(plan),
+                fetched_at: from_timestamp(record.fetched_at),
+                stale: false,
+            }),
+            // the format of the plan might have changed since it was stored
+            Err(why) => {
+                tracing::warn!("couldn't read stored mensa plan of {}: {}", place, why);
+                None
+            }
+        },
+        None => None,
+    })
+}
+
+async fn store_mensa_plan(
+    database: &sqlx::SqlitePool,
+    place: &MensaPlace,
+    plan: &MensaPlan,
+) -> Result<()> {
+    let place_id = place.id().to_string();
+    let content = serde_json::to_string(plan.plan())?;
+    let fetched_at = plan.fetched_at.timestamp();
+    sqlx::query!(
+        "INSERT INTO mensa_plans (place, plan, fetched_at) VALUES (?, ?, ?)
+        ON CONFLICT(place) DO UPDATE SET plan = excluded.plan, fetched_at = excluded.fetched_at",
+        place_id,
+        content,
+        fetched_at,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// the week of a date, counted in weeks since the start of the common era
+fn week_number(days_from_ce: i32) -> i32 {
+    // the first day of the common era is a monday
+    (days_from_ce - 1).div_euclid(7)
+}
+
+/// the week the day is planned in
+pub fn week_of_day(day: &Day) -> i32 {
+    week_number(day.to_chrono().unwrap().num_days_from_ce())
+}
+
+/// the week it currently is in freiburg
+pub fn current_week() -> i32 {
+    week_number(Utc::now().with_timezone(&Berlin).num_days_from_ce())
+}
+
+/// the week that is interesting right now, the next one on the weekend
+pub fn upcoming_week() -> i32 {
+    let now = Utc::now().with_timezone(&Berlin);
+    match now.weekday() {
+        Weekday::Sat | Weekday::Sun => current_week() + 1,
+        _ => current_week(),
+    }
+}
+
+/// the day of the plan, if the plan contains it for `week`
+pub fn plan_day(plan: &Plan, weekday: mensa::Weekday, week: i32) -> Option<&Day> {
+    plan.day(weekday).filter(|day| week_of_day(day) == week)
+}
+
+/// all days of the plan in `week`
+pub fn plan_week(plan: &Plan, week: i32) -> Vec<&Day> {
+    mensa::Weekday::iter()
+        .filter_map(|weekday| plan_day(plan, weekday, week))
+        .collect()
+}
+
+const SWFR_LOGO: &'static str = "https://cloud.nirusu.codes/s/McBDNYTkNjoEFyc/preview";
+const MAX_FIELD_LENGTH: usize = 1000;
+
+/// the dishes of the day that pass the filter, with the price of `tier` or all prices.
+/// the color is left to the caller
+pub fn create_mensa_plan_by_day(
+    day: &mensa::Day,
+    filter: &MenuFilter,
+    tier: Option<PriceTier>,
+    ratings: &HashMap<String, DishRating>,
+) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    embed
+        .title(format!(
+            "{} ({})",
+            &day.weekday()
+                .unwrap_or(Utc::now().weekday().into())
+                .full_name(),
+            day.to_chrono().unwrap().format("%d.%m.%Y")
+        ))
+        .thumbnail(SWFR_LOGO);
+    let mut hidden = 0;
+    for menu in day.menues.iter() {
+        let dish = Dish::parse(&menu.name, menu.food_type.as_deref());
+        if !filter.allows(&dish) {
+            hidden += 1;
+            continue;
+        }
+        let price = &menu.price;
+        let mut text = dish.components.join("\n");
+        if dish.class != FoodClass::Unknown {
+            text.push_str(&format!("\n\nDiet: {}", dish.class.name()));
+        }
+        if !dish.allergens.is_empty() {
+            text.push_str(&format!(
+                "\nAllergens: {}",
+                dish.allergens
+                    .iter()
+                    .map(|allergen| allergen.name())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ));
+        }
+        if !dish.additives.is_empty() {
+            text.push_str(&format!(
+                "\nAdditives: {}",
+                dish.additives
+                    .iter()
+                    .map(|additive| additive_name(*additive))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ));
+        }
+        let prices = [
+            (PriceTier::Student, price.price_students.to_string()),
+            (PriceTier::Employee, price.price_workers.to_string()),
+            (PriceTier::Guest, price.price_guests.to_string()),
+        ];
+        text.push_str(&format!("\n\n{}", format_prices(&prices, tier)));
+        let title = match ratings.get(&dish.key()) {
+            Some(rating) => format!(
+                "{} ({:.1}★ from {})",
+                menu.art, rating.average, rating.count
+            ),
+            None => menu.art.to_string(),
+        };
+        embed.field(title, text, false);
+    }
+    if let Some(description) = filter.describe().filter(|_| hidden > 0) {
+        embed.footer(|f| f.text(format!("{} dishes hidden ({})", hidden, description)));
+    }
+    embed
+}
+
+/// one line per dish of every day in the week, the color is left to the caller
+pub fn create_mensa_plan_by_week(
+    days: &[&mensa::Day],
+    filter: &MenuFilter,
+    tier: Option<PriceTier>,
+) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    embed.thumbnail(SWFR_LOGO);
+    if let (Some(first), Some(last)) = (days.first(), days.last()) {
+        embed.title(format!(
+            "Week {} - {}",
+            first.to_chrono().unwrap().format("%d.%m."),
+            last.to_chrono().unwrap().format("%d.%m.%Y")
+        ));
+    }
+    for day in days {
+        let lines = day
+            .menues
+            .iter()
+            .map(|menu| (menu, Dish::parse(&menu.name, menu.food_type.as_deref())))
+            .filter(|(_, dish)| filter.allows(dish))
+            .map(|(menu, dish)| {
+                let price = &menu.price;
+                let prices = [
+                    (PriceTier::Student, price.price_students.to_string()),
+                    (PriceTier::Employee, price.price_workers.to_string()),
+                    (PriceTier::Guest, price.price_guests.to_string()),
+                ];
+                let mut line = format!("**{}**: {}", menu.art, dish.components.join(", "));
+                if tier.is_some() {
+                    line.push_str(&format!(" ({})", format_prices(&prices, tier)));
+                }
+                line
+            })
+            .collect::<Vec<String>>();
+        let mut text = String::new();
+        for line in lines.iter() {
+            // embed fields can't be longer than 1024 characters
+            if text.len() + line.len() + 1 > MAX_FIELD_LENGTH {
+                text.push_str("\n...");
+                break;
+            }
+            text.push_str(&format!("\n{}", line));
+        }
+        embed.field(
+            format!(
+                "{} ({})",
+                day.weekday()
+                    .unwrap_or(Utc::now().weekday().into())
+                    .full_name(),
+                day.to_chrono().unwrap().format("%d.%m.")
+            ),
+            if text.is_empty() {
+                String::from("nothing for your diet")
+            } else {
+                text
+            },
+            false,
+        );
+    }
+    if days.is_empty() {
+        embed.description("no mensa this week");
+    }
+    embed
+}
+
+/// the plan of the day in the color of the bot, with a note if the plan couldn't be updated
+pub fn embed_mensa_day(
+    mensa: &MensaPlan,
+    place: MensaPlace,
+    day: mensa::Weekday,
+    week: i32,
+    filter: &MenuFilter,
+    tier: Option<PriceTier>,
+    ratings: &HashMap<String, DishRating>,
+) -> serenity::CreateEmbed {
+    let mut embed = plan_day(mensa.plan(), day, week)
+        // the mensa is closed if there is nothing on the plan
+        .filter(|day| !day.menues.is_empty())
+        .map(|day| create_mensa_plan_by_day(day, filter, tier, ratings))
+        .unwrap_or_else(|| embed_mensa_closed(place, day, week));
+    embed.color(crate::color());
+    add_stale_note(&mut embed, mensa);
+    embed
+}
+
+/// the compact plan of the week in the color of the bot, with a note if the plan couldn't be
+/// updated
+pub fn embed_mensa_week(
+    mensa: &MensaPlan,
+    week: i32,
+    filter: &MenuFilter,
+    tier: Option<PriceTier>,
+) -> serenity::CreateEmbed {
+    let mut embed = create_mensa_plan_by_week(&plan_week(mensa.plan(), week), filter, tier);
+    embed.color(crate::color());
+    add_stale_note(&mut embed, mensa);
+    embed
+}
+
+/// why the mensa is closed on the day and when it opens again
+fn embed_mensa_closed(place: MensaPlace, day: mensa::Weekday, week: i32) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    let date = match date_of_day(day, week) {
+        Some(date) => date,
+        None => {
+            embed.description(format!("no mensa on {}", day.full_name()));
+            return embed;
+        }
+    };
+    embed.title(format!("{} ({})", day.full_name(), date.format("%d.%m.%Y")));
+    embed.description(match MENSA_HOURS.closed_reason(&place, date) {
+        Some(reason) => format!("{} is closed: {}", place, reason),
+        None => format!("there is no plan of {} for this day", place),
+    });
+    // search from the shown day on, but a day in the past is no help
+    let from = date
+        .and_hms_opt(0, 0, 0)
+        .and_then(|from| from.and_local_timezone(Berlin).single())
+        .map(|from| from.with_timezone(&Utc))
+        .filter(|from| *from > Utc::now())
+        .unwrap_or_else(Utc::now);
+    if let Some((date, hours)) = MENSA_HOURS.next_opening(&place, from) {
+        embed.field(
+            "Next opening",
+            format!(
+                "{} ({}), {}",
+                date.format("%A"),
+                date.format("%d.%m.%Y"),
+                hours
+            ),
+            false,
+        );
+    }
+    if let Some(hours) = MENSA_HOURS.hours(&place) {
+        let mut days = hours.iter().collect::<Vec<_>>();
+        days.sort_by_key(|(day, _)| day.num_days_from_monday());
+        let text = days
+            .iter()
+            .map(|(day, hours)| format!("{}: {}", day, hours))
+            .collect::<Vec<String>>()
+            .join("\n");
+        embed.field("Opening hours", text, false);
+    }
+    embed
+}
+
+fn add_stale_note(embed: &mut serenity::CreateEmbed, mensa: &MensaPlan) {
+    if let Some(stale_since) = mensa.stale_since() {
+        embed.field(
+            "Outdated",
+            format!(
+                "couldn't reach the swfr, this plan is stale since {}",
+                to_relative_timestamp(stale_since)
+            ),
+            false,
+        );
+    }
+}
+
+/// the opening hours and closures that are used if none are configured
+const BUNDLED_MENSA_HOURS: &str = include_str!("../example/mensa_hours.toml");
+/// how far ahead the next opening of a mensa is searched
+const MAX_CLOSED_DAYS: i64 = 366;
+
+lazy_static! {
+    /// the opening hours of all mensas, from the configured file or the bundled one
+    pub static ref MENSA_HOURS: MensaHours = MensaHours::load(crate::CONFIG.mensa_hours.as_deref());
+}
+
+/// the opening hours file like it's written, see example/mensa_hours.toml
+#[derive(Deserialize, Default)]
+struct MensaHoursFile {
+    #[serde(default)]
+    holidays: Vec<String>,
+    #[serde(default)]
+    closures: Vec<ClosureEntry>,
+    #[serde(default)]
+    places: HashMap<String, HashMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+struct ClosureEntry {
+    from: String,
+    to: Option<String>,
+    reason: String,
+    /// the closure applies to all places if there are none
+    #[serde(default)]
+    places: Vec<String>,
+}
+
+/// the time a mensa is open on a day
+#[derive(Clone, Copy, Debug)]
+pub struct OpeningHours {
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+}
+
+impl OpeningHours {
+    fn parse(hours: &str) -> Option<OpeningHours> {
+        let (open, close) = hours.split_once('-')?;
+        Some(OpeningHours {
+            open: NaiveTime::parse_from_str(open.trim(), "%H:%M").ok()?,
+            close: NaiveTime::parse_from_str(close.trim(), "%H:%M").ok()?,
+        })
+    }
+}
+
+impl std::fmt::Display for OpeningHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} - {}",
+            self.open.format("%H:%M"),
+            self.close.format("%H:%M")
+        )
+    }
+}
+
+/// a time in which one or all mensas are closed
+pub struct Closure {
+    from: NaiveDate,
+    to: NaiveDate,
+    reason: String,
+    /// all places are closed if it's `None`
+    places: Option<Vec<MensaPlace>>,
+}
+
+impl Closure {
+    fn applies(&self, place: &MensaPlace, date: NaiveDate) -> bool {
+        self.from <= date
+            && date <= self.to
+            && self
+                .places
+                .as_ref()
+                .map_or(true, |places| places.contains(place))
+    }
+}
+
+/// a public holiday that recurs every year
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Holiday {
+    /// on the same (month, day) every year
+    Fixed(u32, u32),
+    /// the days from easter sunday, like ascension day 39 days after it
+    Easter(i64),
+}
+
+impl Holiday {
+    fn parse(holiday: &str) -> Option<Holiday> {
+        if let Some(offset) = holiday.trim().strip_prefix("easter") {
+            let offset = offset.trim();
+            return match offset {
+                "" => Some(Holiday::Easter(0)),
+                _ => offset
+                    .strip_prefix('+')
+                    .unwrap_or(offset)
+                    .parse::<i64>()
+                    .ok()
+                    .map(Holiday::Easter),
+            };
+        }
+        let date = NaiveDate::parse_from_str(&format!("2000-{}", holiday), "%Y-%m-%d").ok()?;
+        Some(Holiday::Fixed(date.month(), date.day()))
+    }
+
+    fn is_on(&self, date: NaiveDate) -> bool {
+        match self {
+            Holiday::Fixed(month, day) => date.month() == *month && date.day() == *day,
+            Holiday::Easter(offset) => easter_sunday(date.year())
+                .and_then(|easter| easter.checked_add_signed(Duration::days(*offset)))
+                .is_some_and(|holiday| holiday == date),
+        }
+    }
+}
+
+/// the date of easter sunday in the gregorian calendar
+fn easter_sunday(year: i32) -> Option<NaiveDate> {
+    // the anonymous gregorian algorithm
+    let (a, b, c) = (year % 19, year / 100, year % 100);
+    let (d, e) = (b / 4, b % 4);
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let (i, k) = (c / 4, c % 4);
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let n = h + l - 7 * m + 114;
+    NaiveDate::from_ymd_opt(year, (n / 31) as u32, (n % 31 + 1) as u32)
+}
+
+/// the opening hours and closures of all mensas
+#[derive(Default)]
+pub struct MensaHours {
+    /// the recurring public holidays
+    holidays: Vec<Holiday>,
+    closures: Vec<Closure>,
+    places: HashMap<MensaPlace, HashMap<Weekday, OpeningHours>>,
+}
+
+impl MensaHours {
+    /// reads the opening hours from `path` or uses the bundled ones, broken entries are skipped
+    pub fn load(path: Option<&str>) -> MensaHours {
+        let provider = match path {
+            Some(path) => Toml::file(path),
+            None => Toml::string(BUNDLED_MENSA_HOURS),
+        };
+        match Figment::from(provider).extract::<MensaHoursFile>() {
+            Ok(file) => MensaHours::from(file),
+            Err(why) => {
+                tracing::error!("couldn't read mensa opening hours: {}", why);
+                MensaHours::default()
+            }
+        }
+    }
+
+    /// the opening hours of the place for every weekday it's open
+    pub fn hours(&self, place: &MensaPlace) -> Option<&HashMap<Weekday, OpeningHours>> {
+        self.places.get(place)
+    }
+
+    /// why the place is closed on that date, if we know that it is
+    pub fn closed_reason(&self, place: &MensaPlace, date: NaiveDate) -> Option<String> {
+        if let Some(closure) = self
+            .closures
+            .iter()
+            .find(|closure| closure.applies(place, date))
+        {
+            return Some(closure.reason.clone());
+        }
+        if self.holidays.iter().any(|holiday| holiday.is_on(date)) {
+            return Some(String::from("public holiday"));
+        }
+        match self.hours(place) {
+            Some(hours) if !hours.contains_key(&date.weekday()) => {
+                Some(format!("closed on {}s", date.format("%A")))
+            }
+            _ => None,
+        }
+    }
+
+    /// the next time the place opens from `from` on, if the opening hours are known
+    pub fn next_opening(
+        &self,
+        place: &MensaPlace,
+        from: DateTime<Utc>,
+    ) -> Option<(NaiveDate, OpeningHours)> {
+        let from = from.with_timezone(&Berlin);
+        let hours = self.hours(place)?;
+        (0..MAX_CLOSED_DAYS)
+            .filter_map(|days| from.date_naive().checked_add_signed(Duration::days(days)))
+            .filter(|date| self.closed_reason(place, *date).is_none())
+            .filter_map(|date| hours.get(&date.weekday()).map(|hours| (date, *hours)))
+            // it doesn't open again today after it closed
+            .find(|(date, hours)| *date != from.date_naive() || from.time() < hours.close)
+    }
+}
+
+impl From<MensaHoursFile> for MensaHours {
+    fn from(file: MensaHoursFile) -> MensaHours {
+        let holidays = file
+            .holidays
+            .iter()
+            .filter_map(|holiday| {
+                let parsed = Holiday::parse(holiday);
+                if parsed.is_none() {
+                    tracing::warn!(
+                        "skipping holiday {}, it has to look like 12-24 or easter+39",
+                        holiday
+                    );
+                }
+                parsed
+            })
+            .collect();
+        let closures = file
+            .closures
+            .into_iter()
+            .filter_map(|closure| {
+                let from = NaiveDate::parse_from_str(&closure.from, "%Y-%m-%d").ok();
+                let to = match &closure.to {
+                    Some(to) => NaiveDate::parse_from_str(to, "%Y-%m-%d").ok(),
+                    None => from,
+                };
+                let places = closure
+                    .places
+                    .iter()
+                    .map(|place| find_place(place))
+                    .collect::<Option<Vec<MensaPlace>>>();
+                match (from, to, places) {
+                    (Some(from), Some(to), Some(places)) => Some(Closure {
+                        from,
+                        to,
+                        reason: closure.reason,
+                        places: Some(places).filter(|places| !places.is_empty()),
+                    }),
+                    _ => {
+                        tracing::warn!("skipping invalid mensa closure \"{}\"", closure.reason);
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<Closure>>();
+        // the breaks are different every year, they have to be added to the file
+        let today = Utc::now().with_timezone(&Berlin).date_naive();
+        if !closures.is_empty() && closures.iter().all(|closure| closure.to < today) {
+            tracing::warn!("all mensa closures have passed, add the coming breaks to mensa_hours");
+        }
+        let mut places = HashMap::new();
+        for (name, days) in file.places {
+            let place = match find_place(&name) {
+                Some(place) => place,
+                None => {
+                    tracing::warn!("skipping opening hours of unknown mensa {}", name);
+                    continue;
+                }
+            };
+            let mut hours = HashMap::new();
+            for (day, time) in days {
+                match (day.parse::<Weekday>(), OpeningHours::parse(&time)) {
+                    (Ok(day), Some(time)) => {
+                        hours.insert(day, time);
+                    }
+                    _ => tracing::warn!("skipping opening hours {} = {} of {}", day, time, name),
+                }
+            }
+            places.insert(place, hours);
+        }
+        MensaHours {
+            holidays,
+            closures,
+            places,
+        }
+    }
+}
+
+/// the place with that id or name, ignoring the case
+pub fn find_place(text: &str) -> Option<MensaPlace> {
+    let text = text.to_lowercase();
+    MensaPlace::iter().find(|place| {
+        place.id().to_string().to_lowercase() == text || place.to_string().to_lowercase() == text
+    })
+}
+
+/// the date of the weekday in the week, see `current_week`
+pub fn date_of_day(weekday: mensa::Weekday, week: i32) -> Option<NaiveDate> {
+    // the days of the plan are all weekdays in order, starting with monday
+    let offset = mensa::Weekday::iter().position(|day| day == weekday)? as i32;
+    NaiveDate::from_num_days_from_ce_opt(week * 7 + 1 + offset)
+}
+
+/// a channel that gets the plan of `place` posted every weekday at `post_time`
+pub struct MensaSubscription {
+    pub channel_id: u64,
+    pub place: MensaPlace,
+    pub post_time: NaiveTime,
+}
+
+pub async fn subscribe_mensa(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    channel_id: i64,
+    place: &MensaPlace,
+    post_time: &NaiveTime,
+) -> Result<()> {
+    let place_id = place.id().to_string();
+    let post_time = post_time.format("%H:%M").to_string();
+    // don't post today if the time is already over
+    let now = Utc::now().with_timezone(&Berlin);
+    let last_posted =
+        (now.format("%H:%M").to_string() >= post_time).then(|| now.format("%Y-%m-%d").to_string());
+    sqlx::query!(
+        "INSERT INTO mensa_subscriptions (channel_id, guild_id, place, post_time, last_posted) VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT(channel_id) DO UPDATE SET place = excluded.place, post_time = excluded.post_time, last_posted = excluded.last_posted",
+        channel_id,
+        guild_id,
+        place_id,
+        post_time,
+        last_posted,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+pub async fn unsubscribe_mensa(database: &sqlx::SqlitePool, channel_id: i64) -> Result<()> {
+    let result = sqlx::query!(
+        "DELETE FROM mensa_subscriptions WHERE channel_id = ?",
+        channel_id
+    )
+    .execute(database)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(Box::new(AYError::InvalidInput(
+            "this channel isn't subscribed to the mensa plan",
+        )));
+    }
+    Ok(())
+}
+
+/// all subscriptions that weren't posted on `date` although `time` is over
+async fn get_due_mensa_subscriptions(
+    database: &sqlx::SqlitePool,
+    date: &str,
+    time: &str,
+) -> Result<Vec<MensaSubscription>> {
+    let records = sqlx::query!(
+        "SELECT channel_id, place, post_time FROM mensa_subscriptions WHERE post_time <= ? AND (last_posted IS NULL OR last_posted < ?)",
+        time,
+        date
+    )
+    .fetch_all(database)
+    .await?;
+    let mut subscriptions = vec![];
+    for record in records {
+        let (place, post_time) = match (
+            MensaPlace::try_from(record.place.as_str()),
+            NaiveTime::parse_from_str(&record.post_time, "%H:%M"),
+        ) {
+            (Ok(place), Ok(post_time)) => (place, post_time),
+            _ => {
+                tracing::warn!("invalid mensa subscription in <#{}>", record.channel_id);
+                continue;
+            }
+        };
+        subscriptions.push(MensaSubscription {
+            channel_id: record.channel_id as u64,
+            place,
+            post_time,
+        });
+    }
+    Ok(subscriptions)
+}
+
+async fn set_mensa_posted(database: &sqlx::SqlitePool, channel_id: i64, date: &str) -> Result<()> {
+    sqlx::query!(
+        "UPDATE mensa_subscriptions SET last_posted = ? WHERE channel_id = ?",
+        date,
+        channel_id
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// posts the plan of the day to the subscribed channels on weekdays
+pub async fn post_subscribed_mensa_plans(
+    ctx: serenity::Context,
+    database: sqlx::SqlitePool,
+    cache: Arc<MensaCache>,
+) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let now = Utc::now().with_timezone(&Berlin);
+        if matches!(now.weekday(), Weekday::Sat | Weekday::Sun) {
+            continue;
+        }
+        let date = now.format("%Y-%m-%d").to_string();
+        let time = now.format("%H:%M").to_string();
+        let subscriptions = match get_due_mensa_subscriptions(&database, &date, &time).await {
+            Ok(subscriptions) => subscriptions,
+            Err(why) => {
+                tracing::error!("couldn't get mensa subscriptions: {}", why);
+                continue;
+            }
+        };
+        for subscription in subscriptions {
+            let mensa = match cache.mensa_plan(&database, &subscription.place).await {
+                Ok(mensa) => mensa,
+                // try again with the next tick
+                Err(why) => {
+                    tracing::error!("couldn't get mensa plan of {}: {}", subscription.place, why);
+                    continue;
+                }
+            };
+            // the mensa is closed if there is nothing on the plan
+            let week = current_week();
+            let open = plan_day(mensa.plan(), now.weekday().into(), week)
+                .map_or(false, |day| !day.menues.is_empty());
+            if open {
+                let ratings = get_dish_ratings(&database, &subscription.place)
+                    .await
+                    .unwrap_or_default();
+                let embed = embed_mensa_day(
+                    &mensa,
+                    subscription.place,
+                    now.weekday().into(),
+                    week,
+                    &MenuFilter::default(),
+                    None,
+                    &ratings,
+                );
+                if let Err(why) = serenity::ChannelId(subscription.channel_id)
+                    .send_message(&ctx, |m| m.set_embed(embed))
+                    .await
+                {
+                    tracing::error!(
+                        "couldn't post mensa plan to <#{}>: {}",
+                        subscription.channel_id,
+                        why
+                    );
+                }
+            }
+            if let Err(why) =
+                set_mensa_posted(&database, subscription.channel_id as i64, &date).await
+            {
+                tracing::error!("couldn't update mensa subscription: {}", why);
+            }
+        }
+    }
+}
+
+/// how the swfr classifies a dish
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FoodClass {
+    Vegan,
+    Vegetarian,
+    Meat,
+    Unknown,
+}
+
+impl FoodClass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Vegan => "vegan",
+            Self::Vegetarian => "vegetarian",
+            Self::Meat => "meat or fish",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+impl From<Option<&str>> for FoodClass {
+    fn from(food_type: Option<&str>) -> FoodClass {
+        match food_type.map(str::to_lowercase) {
+            Some(food_type) if food_type.contains("vegan") => FoodClass::Vegan,
+            Some(food_type) if food_type.contains("vegetar") => FoodClass::Vegetarian,
+            Some(food_type) if !food_type.trim().is_empty() => FoodClass::Meat,
+            _ => FoodClass::Unknown,
+        }
+    }
+}
+
+/// the allergens marked in the dishes of the swfr
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, strum_macros::EnumIter)]
+pub enum Allergen {
+    Gluten,
+    Crustaceans,
+    Eggs,
+    Fish,
+    Peanuts,
+    Soy,
+    Milk,
+    Nuts,
+    Celery,
+    Mustard,
+    Sesame,
+    Sulphites,
+    Lupin,
+    Molluscs,
+}
+
+impl Allergen {
+    /// the code of the swfr, the kinds of gluten and nuts have a longer code with this prefix
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Gluten => "Gl",
+            Self::Crustaceans => "Kr",
+            Self::Eggs => "Ei",
+            Self::Fish => "Fi",
+            Self::Peanuts => "En",
+            Self::Soy => "So",
+            Self::Milk => "ML",
+            Self::Nuts => "Nu",
+            Self::Celery => "Se",
+            Self::Mustard => "Sf",
+            Self::Sesame => "Sa",
+            Self::Sulphites => "Sw",
+            Self::Lupin => "Lu",
+            Self::Molluscs => "Wt",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Gluten => "gluten",
+            Self::Crustaceans => "crustaceans",
+            Self::Eggs => "eggs",
+            Self::Fish => "fish",
+            Self::Peanuts => "peanuts",
+            Self::Soy => "soy",
+            Self::Milk => "milk",
+            Self::Nuts => "nuts",
+            Self::Celery => "celery",
+            Self::Mustard => "mustard",
+            Self::Sesame => "sesame",
+            Self::Sulphites => "sulphites",
+            Self::Lupin => "lupin",
+            Self::Molluscs => "molluscs",
+        }
+    }
+
+    /// the kinds of the allergen, appended to its code like `GlW` for wheat
+    fn kinds(&self) -> &'static [&'static str] {
+        match self {
+            // wheat, rye, barley, oats, spelt, kamut
+            Self::Gluten => &["W", "R", "G", "H", "D", "K"],
+            // almonds, hazelnuts, walnuts, cashews, pecans, brazil nuts, pistachios, macadamias
+            Self::Nuts => &["M", "H", "W", "C", "Pe", "Pa", "Pi", "Ma"],
+            _ => &[],
+        }
+    }
+
+    /// the allergen of a code in a dish, words like `Nudeln` aren't codes
+    pub fn from_code(code: &str) -> Option<Allergen> {
+        Allergen::iter().find(|allergen| {
+            code == allergen.code()
+                || code
+                    .strip_prefix(allergen.code())
+                    .is_some_and(|kind| allergen.kinds().contains(&kind))
+        })
+    }
+
+    /// the allergen of a name or code from user input
+    pub fn from_name(name: &str) -> Option<Allergen> {
+        let name = name.trim().to_lowercase();
+        Allergen::iter()
+            .find(|allergen| allergen.name() == name || allergen.code().to_lowercase() == name)
+    }
+}
+
+/// parses a comma separated list of allergens
+pub fn parse_allergens(list: &str) -> Result<Vec<Allergen>> {
+    let mut allergens = vec![];
+    for name in list.split(',').filter(|name| !name.trim().is_empty()) {
+        let allergen =
+            Allergen::from_name(name).ok_or(AYError::InvalidInput("unknown allergen"))?;
+        if !allergens.contains(&allergen) {
+            allergens.push(allergen);
+        }
+    }
+    Ok(allergens)
+}
+
+/// the name of an additive by its number on the plan
+pub fn additive_name(additive: u8) -> String {
+    match additive {
+        1 => String::from("colouring"),
+        2 => String::from("preservatives"),
+        3 => String::from("antioxidants"),
+        4 => String::from("flavour enhancers"),
+        5 => String::from("sulphurated"),
+        6 => String::from("blackened"),
+        7 => String::from("waxed"),
+        8 => String::from("phosphate"),
+        9 => String::from("sweeteners"),
+        10 => String::from("phenylalanine"),
+        _ => format!("additive {}", additive),
+    }
+}
+
+/// a menu of the plan with the codes split from the name
+#[derive(Clone, Debug)]
+pub struct Dish {
+    pub components: Vec<String>,
+    pub class: FoodClass,
+    pub allergens: Vec<Allergen>,
+    pub additives: Vec<u8>,
+}
+
+impl Dish {
+    /// the name that identifies the dish on other days, without codes and case
+    pub fn key(&self) -> String {
+        self.components.join(" ").to_lowercase()
+    }
+
+    pub fn parse(name: &str, food_type: Option<&str>) -> Dish {
+        let codes = Regex::new(r"\(([^()]*)\)").unwrap();
+        let mut allergens = vec![];
+        let mut additives = vec![];
+        let name = codes.replace_all(name, |captures: &regex::Captures| {
+            let tokens = captures[1]
+                .split(',')
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .collect::<Vec<&str>>();
+            // keep the brackets if they don't only contain codes, like (saisonal)
+            let is_code =
+                |token: &&str| token.parse::<u8>().is_ok() || Allergen::from_code(token).is_some();
+            if tokens.is_empty() || !tokens.iter().all(is_code) {
+                return captures[0].to_string();
+            }
+            for token in tokens {
+                match token.parse::<u8>() {
+                    Ok(additive) if !additives.contains(&additive) => additives.push(additive),
+                    Ok(_) => {}
+                    Err(_) => {
+                        if let Some(allergen) = Allergen::from_code(token) {
+                            if !allergens.contains(&allergen) {
+                                allergens.push(allergen);
+                            }
+                        }
+                    }
+                }
+            }
+            String::new()
+        });
+        let components = Regex::new(r"--+|\n")
+            .unwrap()
+            .split(&name)
+            .map(|component| {
+                component
+                    .split_whitespace()
+                    .collect::<Vec<&str>>()
+                    .join(" ")
+            })
+            .filter(|component| !component.is_empty())
+            .collect();
+        additives.sort_unstable();
+        Dish {
+            components,
+            class: FoodClass::from(food_type),
+            allergens,
+            additives,
+        }
+    }
+}
+
+/// the diet a user wants to see dishes for
+#[derive(Clone, Copy, PartialEq, Debug, poise::ChoiceParameter)]
+pub enum Diet {
+    #[name = "all"]
+    All,
+    #[name = "vegetarian"]
+    Vegetarian,
+    #[name = "vegan"]
+    Vegan,
+}
+
+impl Diet {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Vegetarian => "vegetarian",
+            Self::Vegan => "vegan",
+        }
+    }
+
+    pub fn allows(&self, class: FoodClass) -> bool {
+        match self {
+            Self::All => true,
+            Self::Vegetarian => matches!(class, FoodClass::Vegetarian | FoodClass::Vegan),
+            Self::Vegan => class == FoodClass::Vegan,
+        }
+    }
+}
+
+impl From<&str> for Diet {
+    fn from(kind: &str) -> Diet {
+        match kind {
+            "vegetarian" => Diet::Vegetarian,
+            "vegan" => Diet::Vegan,
+            _ => Diet::All,
+        }
+    }
+}
+
+/// which dishes of the plan are shown
+#[derive(Clone, Debug)]
+pub struct MenuFilter {
+    pub diet: Diet,
+    pub excluded: Vec<Allergen>,
+}
+
+impl Default for MenuFilter {
+    /// shows every dish
+    fn default() -> Self {
+        MenuFilter {
+            diet: Diet::All,
+            excluded: vec![],
+        }
+    }
+}
+
+impl MenuFilter {
+    pub fn allows(&self, dish: &Dish) -> bool {
+        self.diet.allows(dish.class)
+            && !dish
+                .allergens
+                .iter()
+                .any(|allergen| self.excluded.contains(allergen))
+    }
+
+    /// the filter in words, `None` if every dish is shown
+    pub fn describe(&self) -> Option<String> {
+        let mut parts = vec![];
+        if self.diet != Diet::All {
+            parts.push(self.diet.kind().to_string());
+        }
+        if !self.excluded.is_empty() {
+            parts.push(format!(
+                "without {}",
+                self.excluded
+                    .iter()
+                    .map(|allergen| allergen.name())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ));
+        }
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
+/// the saved diet preferences of the user, everything is shown if there are none
+pub async fn get_menu_filter(database: &sqlx::SqlitePool, user_id: i64) -> Result<MenuFilter> {
+    Ok(sqlx::query!(
+        "SELECT diet, excluded_allergens FROM mensa_preferences WHERE user_id = ?",
+        user_id
+    )
+    .fetch_optional(database)
+    .await?
+    .map(|record| MenuFilter {
+        diet: Diet::from(record.diet.as_str()),
+        excluded: record
+            .excluded_allergens
+            .split(',')
+            .filter_map(Allergen::from_name)
+            .collect(),
+    })
+    .unwrap_or_default())
+}
+
+pub async fn set_menu_filter(
+    database: &sqlx::SqlitePool,
+    user_id: i64,
+    filter: &MenuFilter,
+) -> Result<()> {
+    let diet = filter.diet.kind();
+    let excluded = filter
+        .excluded
+        .iter()
+        .map(|allergen| allergen.code())
+        .collect::<Vec<&str>>()
+        .join(",");
+    sqlx::query!(
+        "INSERT INTO mensa_preferences (user_id, diet, excluded_allergens) VALUES (?, ?, ?)
+        ON CONFLICT(user_id) DO UPDATE SET diet = excluded.diet, excluded_allergens = excluded.excluded_allergens",
+        user_id,
+        diet,
+        excluded,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// the price a user pays in the mensa
+#[derive(Clone, Copy, PartialEq, Debug, strum_macros::EnumIter, poise::ChoiceParameter)]
+pub enum PriceTier {
+    #[name = "student"]
+    Student,
+    #[name = "employee"]
+    Employee,
+    #[name = "guest"]
+    Guest,
+}
+
+impl PriceTier {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Student => "student",
+            Self::Employee => "employee",
+            Self::Guest => "guest",
+        }
+    }
+
+    pub fn from_kind(kind: &str) -> Option<PriceTier> {
+        PriceTier::iter().find(|tier| tier.kind() == kind)
+    }
+}
+
+/// a price of the plan like `3,50` as euros
+pub fn format_euros(price: &str) -> String {
+    let amount = price.trim().trim_end_matches('€').trim().replace(',', ".");
+    match amount.parse::<f64>() {
+        Ok(amount) => format!("{:.2} €", amount).replace('.', ","),
+        Err(_) => price.to_string(),
+    }
+}
+
+/// the price of `tier`, or every price labeled if the tier isn't known
+pub fn format_prices(prices: &[(PriceTier, String)], tier: Option<PriceTier>) -> String {
+    match prices
+        .iter()
+        .find(|(price_tier, _)| Some(*price_tier) == tier)
+    {
+        Some((tier, price)) => format!("Price: {} ({})", format_euros(price), tier.kind()),
+        None => prices
+            .iter()
+            .map(|(tier, price)| format!("{}: {}", tier.kind(), format_euros(price)))
+            .collect::<Vec<String>>()
+            .join(" | "),
+    }
+}
+
+pub async fn get_price_tier(
+    database: &sqlx::SqlitePool,
+    user_id: i64,
+) -> Result<Option<PriceTier>> {
+    Ok(sqlx::query!(
+        "SELECT price_tier FROM mensa_preferences WHERE user_id = ?",
+        user_id
+    )
+    .fetch_optional(database)
+    .await?
+    .and_then(|record| record.price_tier)
+    .and_then(|kind| PriceTier::from_kind(&kind)))
+}
+
+pub async fn set_price_tier(
+    database: &sqlx::SqlitePool,
+    user_id: i64,
+    tier: Option<PriceTier>,
+) -> Result<()> {
+    let tier = tier.map(|tier| tier.kind());
+    sqlx::query!(
+        "INSERT INTO mensa_preferences (user_id, price_tier) VALUES (?, ?)
+        ON CONFLICT(user_id) DO UPDATE SET price_tier = excluded.price_tier",
+        user_id,
+        tier,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_guild_mensa_place(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+) -> Result<Option<MensaPlace>> {
+    Ok(sqlx::query!(
+        "SELECT mensa_place FROM guild_settings WHERE guild_id = ?",
+        guild_id
+    )
+    .fetch_optional(database)
+    .await?
+    .and_then(|record| record.mensa_place)
+    .and_then(|place| MensaPlace::try_from(place.as_str()).ok()))
+}
+
+pub async fn set_guild_mensa_place(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    place: Option<MensaPlace>,
+) -> Result<()> {
+    let place = place.map(|place| place.id().to_string());
+    sqlx::query!(
+        "INSERT INTO guild_settings (guild_id, mensa_place) VALUES (?, ?)
+        ON CONFLICT(guild_id) DO UPDATE SET mensa_place = excluded.mensa_place",
+        guild_id,
+        place,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_user_mensa_place(
+    database: &sqlx::SqlitePool,
+    user_id: i64,
+) -> Result<Option<MensaPlace>> {
+    Ok(sqlx::query!(
+        "SELECT place FROM mensa_preferences WHERE user_id = ?",
+        user_id
+    )
+    .fetch_optional(database)
+    .await?
+    .and_then(|record| record.place)
+    .and_then(|place| MensaPlace::try_from(place.as_str()).ok()))
+}
+
+pub async fn set_user_mensa_place(
+    database: &sqlx::SqlitePool,
+    user_id: i64,
+    place: Option<MensaPlace>,
+) -> Result<()> {
+    let place = place.map(|place| place.id().to_string());
+    sqlx::query!(
+        "INSERT INTO mensa_preferences (user_id, place) VALUES (?, ?)
+        ON CONFLICT(user_id) DO UPDATE SET place = excluded.place",
+        user_id,
+        place,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// the place of the user, else the place of the guild, else the default place
+pub async fn resolve_mensa_place(
+    database: &sqlx::SqlitePool,
+    guild_id: Option<i64>,
+    user_id: i64,
+) -> Result<MensaPlace> {
+    if let Some(place) = get_user_mensa_place(database, user_id).await? {
+        return Ok(place);
+    }
+    if let Some(guild_id) = guild_id {
+        if let Some(place) = get_guild_mensa_place(database, guild_id).await? {
+            return Ok(place);
+        }
+    }
+    Ok(DEFAULT_PLACE)
+}
+
+/// if every word of the query is in the text or a word of the text is close to it
+pub fn fuzzy_matches(query: &str, text: &str) -> bool {
+    let text = text.to_lowercase();
+    let words = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>();
+    query.to_lowercase().split_whitespace().all(|term| {
+        // a typo every four characters is fine
+        let allowed = term.chars().count() / 4;
+        text.contains(term) || words.iter().any(|word| levenshtein(term, word) <= allowed)
+    })
+}
+
+/// the number of edits to get from `a` to `b`
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut distances = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, char_a) in a.chars().enumerate() {
+        let mut previous = distances[0];
+        distances[0] = i + 1;
+        for (j, char_b) in b.iter().enumerate() {
+            let current = distances[j + 1];
+            distances[j + 1] = if char_a == *char_b {
+                previous
+            } else {
+                1 + previous.min(current).min(distances[j])
+            };
+            previous = current;
+        }
+    }
+    distances[b.len()]
+}
+
+/// the average rating of a dish
+#[derive(Clone, Copy, Debug)]
+pub struct DishRating {
+    pub average: f64,
+    pub count: i64,
+}
+
+/// a dish with its rating on the leaderboard
+pub struct TopDish {
+    pub place: String,
+    pub name: String,
+    pub rating: DishRating,
+    pub days: i64,
+}
+
+/// stores the rating of the user, the rating is changed if the user rated it on that day
+pub async fn rate_dish(
+    database: &sqlx::SqlitePool,
+    user_id: i64,
+    place: &MensaPlace,
+    dish: &Dish,
+    served_on: &str,
+    rating: u8,
+) -> Result<()> {
+    if !(1..=5).contains(&rating) {
+        return Err(Box::new(AYError::InvalidInput(
+            "ratings have to be between 1 and 5",
+        )));
+    }
+    let place = place.id().to_string();
+    let key = dish.key();
+    let name = dish.components.join(", ");
+    let rating = rating as i64;
+    sqlx::query!(
+        "INSERT INTO dish_ratings (user_id, place, dish, name, served_on, rating) VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT(user_id, place, dish, served_on) DO UPDATE SET rating = excluded.rating",
+        user_id,
+        place,
+        key,
+        name,
+        served_on,
+        rating,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// the ratings of all dishes of the place by `Dish::key`
+pub async fn get_dish_ratings(
+    database: &sqlx::SqlitePool,
+    place: &MensaPlace,
+) -> Result<HashMap<String, DishRating>> {
+    let place = place.id().to_string();
+    Ok(sqlx::query!(
+        r#"SELECT dish, AVG(rating) AS "average!: f64", COUNT(*) AS "count!: i64"
+        FROM dish_ratings WHERE place = ? GROUP BY dish"#,
+        place
+    )
+    .fetch_all(database)
+    .await?
+    .into_iter()
+    .map(|record| {
+        (
+            record.dish,
+            DishRating {
+                average: record.average,
+                count: record.count,
+            },
+        )
+    })
+    .collect())
+}
+
+/// the best rated dishes that were served on more than one day
+pub async fn get_top_dishes(
+    database: &sqlx::SqlitePool,
+    place: Option<&MensaPlace>,
+    limit: i64,
+) -> Result<Vec<TopDish>> {
+    let place = place.map(|place| place.id().to_string());
+    Ok(sqlx::query!(
+        r#"SELECT place, MAX(name) AS "name!: String", AVG(rating) AS "average!: f64",
+        COUNT(*) AS "count!: i64", COUNT(DISTINCT served_on) AS "days!: i64"
+        FROM dish_ratings WHERE ? IS NULL OR place = ?
+        GROUP BY place, dish HAVING COUNT(DISTINCT served_on) > 1
+        ORDER BY AVG(rating) DESC, COUNT(*) DESC LIMIT ?"#,
+        place,
+        place,
+        limit
+    )
+    .fetch_all(database)
+    .await?
+    .into_iter()
+    .map(|record| TopDish {
+        place: match MensaPlace::try_from(record.place.as_str()) {
+            Ok(place) => place.to_string(),
+            Err(_) => record.place.clone(),
+        },
+        name: record.name,
+        rating: DishRating {
+            average: record.average,
+            count: record.count,
+        },
+        days: record.days,
+    })
+    .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration as StdDuration,
+    };
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn easter_holidays_move_with_easter() {
+        assert_eq!(easter_sunday(2024), Some(date("2024-03-31")));
+        assert_eq!(easter_sunday(2025), Some(date("2025-04-20")));
+        assert_eq!(easter_sunday(2026), Some(date("2026-04-05")));
+        let ascension = Holiday::parse("easter+39").unwrap();
+        assert!(ascension.is_on(date("2024-05-09")));
+        assert!(ascension.is_on(date("2025-05-29")));
+        assert!(!ascension.is_on(date("2025-05-09")));
+        assert!(Holiday::parse("easter-2")
+            .unwrap()
+            .is_on(date("2026-04-03")));
+    }
+
+    #[test]
+    fn bundled_hours_know_the_holidays_of_every_year() {
+        let hours = MensaHours::from(
+            Figment::from(Toml::string(BUNDLED_MENSA_HOURS))
+                .extract::<MensaHoursFile>()
+                .unwrap(),
+        );
+        let place = MensaPlace::iter().next().unwrap();
+        for holiday in [
+            "2030-01-01",
+            "2030-04-19",
+            "2030-05-30",
+            "2030-06-10",
+            "2030-06-20",
+        ] {
+            assert_eq!(
+                hours.closed_reason(&place, date(holiday)),
+                Some(String::from("public holiday")),
+                "{}",
+                holiday
+            );
+        }
+    }
+
+    #[test]
+    fn allergen_codes_and_kinds() {
+        assert_eq!(Allergen::from_code("Gl"), Some(Allergen::Gluten));
+        assert_eq!(Allergen::from_code("GlW"), Some(Allergen::Gluten));
+        assert_eq!(Allergen::from_code("NuH"), Some(Allergen::Nuts));
+        assert_eq!(Allergen::from_code("NuPi"), Some(Allergen::Nuts));
+        assert_eq!(Allergen::from_code("Sf"), Some(Allergen::Mustard));
+        assert_eq!(Allergen::from_code("SfX"), None);
+    }
+
+    #[test]
+    fn words_in_brackets_are_no_codes() {
+        for word in ["Nudeln", "Senf", "Sahne", "Soße"] {
+            assert_eq!(Allergen::from_code(word), None);
+            let dish = Dish::parse(&format!("Linsen ({})", word), None);
+            assert_eq!(dish.components, vec![format!("Linsen ({})", word)]);
+            assert!(dish.allergens.is_empty());
+        }
+    }
+
+    #[test]
+    fn codes_are_split_from_the_name() {
+        let dish = Dish::parse("Spaghetti (GlW,Ei,3) -- Salat (NuH,Sf)", None);
+        assert_eq!(dish.components, vec!["Spaghetti", "Salat"]);
+        assert_eq!(
+            dish.allergens,
+            vec![
+                Allergen::Gluten,
+                Allergen::Eggs,
+                Allergen::Nuts,
+                Allergen::Mustard
+            ]
+        );
+        assert_eq!(dish.additives, vec![3]);
+    }
+
+    /// fails every request and counts them
+    struct FailingSource(Arc<AtomicUsize>);
+
+    impl PlanSource for FailingSource {
+        fn request<'a>(&'a self, _place: &'a MensaPlace) -> PlanRequest<'a> {
+            Box::pin(async move {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(StdDuration::from_millis(50)).await;
+                Err(AYError::Unavailable("the swfr is down", None).into())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_requests_are_shared_and_not_repeated() {
+        // every connection to an in-memory database has its own database
+        let database = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&database).await.unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let cache = MensaCache::new(Box::new(FailingSource(requests.clone())), 60);
+        let place = MensaPlace::Rempartstraße;
+
+        let (first, second) = tokio::join!(
+            cache.mensa_plan(&database, &place),
+            cache.mensa_plan(&database, &place)
+        );
+        assert!(first.is_err() && second.is_err());
+        assert!(cache.mensa_plan(&database, &place).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert!(cache.requests.lock().await.is_empty());
+    }
+
+    /// the recorded plan of the rempartstraße
+    fn recorded_plan() -> mensa::Plan {
+        serde_xml_rs::from_str(include_str!("../fixtures/mensa/610.xml")).expect("fixture plan")
+    }
+
+    fn field_names(embed: &serenity::CreateEmbed) -> Vec<String> {
+        embed.0["fields"]
+            .as_array()
+            .map(|fields| {
+                fields
+                    .iter()
+                    .map(|field| field["name"].as_str().unwrap().to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn field_text(embed: &serenity::CreateEmbed, index: usize) -> String {
+        embed.0["fields"][index]["value"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn mensa_plan_of_a_recorded_day() {
+        let plan = recorded_plan();
+        let monday = plan
+            .day(mensa::Weekday::from(chrono::Weekday::Mon))
+            .unwrap();
+        let embed = create_mensa_plan_by_day(monday, &MenuFilter::default(), None, &HashMap::new());
+        assert!(embed.0["title"].as_str().unwrap().ends_with("(05.06.2023)"));
+        assert_eq!(field_names(&embed), vec!["Essen 1", "Essen 2", "Buffet"]);
+        let text = field_text(&embed, 1);
+        assert!(text.starts_with("Schweinegeschnetzeltes in Rahmsoße\nSpätzle\nSalat (Senf)"));
+        assert!(text.contains("Allergens: milk, celery, gluten, eggs"));
+        assert!(text.contains("3,60 €") && text.contains("7,60 €"));
+        assert!(!embed.0.contains_key("footer"));
+    }
+
+    #[test]
+    fn mensa_plan_of_a_recorded_day_with_a_filter_and_price_tier() {
+        let plan = recorded_plan();
+        let monday = plan
+            .day(mensa::Weekday::from(chrono::Weekday::Mon))
+            .unwrap();
+        let filter = MenuFilter {
+            diet: Diet::Vegetarian,
+            excluded: vec![Allergen::Nuts],
+        };
+        let ratings = HashMap::from([(
+            String::from("linsen-dal basmatireis gurkensalat"),
+            DishRating {
+                average: 4.5,
+                count: 2,
+            },
+        )]);
+        let embed = create_mensa_plan_by_day(monday, &filter, Some(PriceTier::Student), &ratings);
+        assert_eq!(field_names(&embed), vec!["Essen 1 (4.5★ from 2)"]);
+        let text = field_text(&embed, 0);
+        assert!(text.ends_with("Price: 3,20 € (student)"));
+        assert!(embed.0["footer"]["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("2 dishes hidden"));
+    }
+}
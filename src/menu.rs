@@ -37,6 +37,7 @@ impl<'a, T> Menu<'a, T> {
         while let Some(mci) =
             serenity::CollectComponentInteraction::new(self.ctx.serenity_context())
                 .channel_id(self.ctx.channel_id())
+                .message_id(mes.id)
                 .timeout(std::time::Duration::from_secs(self.options.timeout))
                 .await
         {
@@ -259,7 +260,7 @@ pub enum MenuComponent {
 }
 
 impl MenuComponent {
-    fn id(&self) -> String {
+    pub fn id(&self) -> String {
         match self {
             Self::ButtonComponent { create: _, id } => id,
             Self::SelectComponent { create: _, id } => id,
@@ -351,6 +352,25 @@ impl<'a, T> Cursor<'a, T> {
     pub fn current(&self) -> Option<&'a T> {
         self.list.get(self.current_index)
     }
+
+    pub fn select(&mut self, index: usize) -> Option<&'a T> {
+        if index < self.list.len() {
+            self.current_index = index;
+        }
+        self.list.get(self.current_index)
+    }
+
+    pub fn index(&self) -> usize {
+        self.current_index
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
 }
 
 impl<'a, T> From<&'a Vec<T>> for Cursor<'a, T> {
@@ -0,0 +1,264 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{util::get_simulate_channel, Result};
+use lazy_static::lazy_static;
+use poise::{async_trait, serenity_prelude as serenity};
+
+lazy_static! {
+    /// the last simulated action of every overwrite by channel and member,
+    /// every event would simulate the same overwrites again otherwise
+    static ref SIMULATED: Mutex<HashMap<(u64, String), OverwriteAction>> =
+        Mutex::new(HashMap::new());
+}
+
+/// a side effect of the exclusion engine
+#[derive(Clone, Debug, PartialEq)]
+pub enum OverwriteAction {
+    Create {
+        channel_id: serenity::ChannelId,
+        overwrite: serenity::PermissionOverwrite,
+    },
+    Delete {
+        channel_id: serenity::ChannelId,
+        kind: serenity::PermissionOverwriteType,
+    },
+    Disconnect {
+        guild_id: serenity::GuildId,
+        user: u64,
+    },
+}
+
+impl OverwriteAction {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Create {
+                channel_id,
+                overwrite,
+            } => format!(
+                "set overwrite for {} in <#{}> (allow: {}, deny: {})",
+                mention(&overwrite.kind),
+                channel_id,
+                overwrite.allow,
+                overwrite.deny
+            ),
+            Self::Delete { channel_id, kind } => {
+                format!(
+                    "delete overwrite for {} in <#{}>",
+                    mention(kind),
+                    channel_id
+                )
+            }
+            Self::Disconnect { user, .. } => format!("disconnect <@{}> from voice", user),
+        }
+    }
+}
+
+fn mention(kind: &serenity::PermissionOverwriteType) -> String {
+    match kind {
+        serenity::PermissionOverwriteType::Member(user) => format!("<@{}>", user),
+        serenity::PermissionOverwriteType::Role(role) => format!("<@&{}>", role),
+        _ => String::from("unknown"),
+    }
+}
+
+/// everything the exclusion engine changes on discord goes through this trait
+#[async_trait]
+pub trait Overwrites: Send + Sync {
+    /// if the overwrites actually end up on discord and should be recorded
+    fn applies(&self) -> bool {
+        true
+    }
+
+    async fn create(
+        &self,
+        channel: &serenity::GuildChannel,
+        overwrite: serenity::PermissionOverwrite,
+    ) -> Result<()>;
+
+    async fn delete(
+        &self,
+        channel: &serenity::GuildChannel,
+        kind: serenity::PermissionOverwriteType,
+    ) -> Result<()>;
+
+    async fn disconnect(&self, guild_id: serenity::GuildId, user: u64) -> Result<()>;
+}
+
+/// the overwrites of the guild, simulated if the guild has a simulation channel
+pub async fn guild_overwrites(
+    ctx: &serenity::Context,
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+) -> Result<Box<dyn Overwrites>> {
+    Ok(match get_simulate_channel(database, guild_id).await? {
+        Some(channel) => Box::new(SimulatedOverwrites::new(
+            ctx.http.clone(),
+            serenity::ChannelId(channel),
+        )),
+        None => Box::new(DiscordOverwrites::new(ctx.http.clone())),
+    })
+}
+
+/// applies the overwrites with the discord api
+pub struct DiscordOverwrites {
+    http: Arc<serenity::Http>,
+}
+
+impl DiscordOverwrites {
+    pub fn new(http: Arc<serenity::Http>) -> Self {
+        Self { http }
+    }
+}
+
+#[async_trait]
+impl Overwrites for DiscordOverwrites {
+    async fn create(
+        &self,
+        channel: &serenity::GuildChannel,
+        overwrite: serenity::PermissionOverwrite,
+    ) -> Result<()> {
+        channel.create_permission(&self.http, &overwrite).await?;
+        Ok(())
+    }
+
+    async fn delete(
+        &self,
+        channel: &serenity::GuildChannel,
+        kind: serenity::PermissionOverwriteType,
+    ) -> Result<()> {
+        channel.delete_permission(&self.http, kind).await?;
+        Ok(())
+    }
+
+    async fn disconnect(&self, guild_id: serenity::GuildId, user: u64) -> Result<()> {
+        guild_id.disconnect_member(&self.http, user).await?;
+        Ok(())
+    }
+}
+
+/// logs the overwrites to a channel instead of applying them
+pub struct SimulatedOverwrites {
+    http: Arc<serenity::Http>,
+    log_channel: serenity::ChannelId,
+}
+
+impl SimulatedOverwrites {
+    pub fn new(http: Arc<serenity::Http>, log_channel: serenity::ChannelId) -> Self {
+        Self { http, log_channel }
+    }
+
+    /// logs the action, overwrites only if they changed since they were logged the last time
+    async fn log(&self, action: OverwriteAction) -> Result<()> {
+        let key = match &action {
+            OverwriteAction::Create {
+                channel_id,
+                overwrite,
+            } => Some((channel_id.0, mention(&overwrite.kind))),
+            OverwriteAction::Delete { channel_id, kind } => Some((channel_id.0, mention(kind))),
+            // every disconnect is a new join of the user
+            OverwriteAction::Disconnect { .. } => None,
+        };
+        if let Some(key) = &key {
+            if SIMULATED.lock().unwrap().get(key) == Some(&action) {
+                return Ok(());
+            }
+        }
+        self.log_channel
+            .say(&self.http, format!("Simulation: {}", action.describe()))
+            .await?;
+        if let Some(key) = key {
+            SIMULATED.lock().unwrap().insert(key, action);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Overwrites for SimulatedOverwrites {
+    fn applies(&self) -> bool {
+        false
+    }
+
+    async fn create(
+        &self,
+        channel: &serenity::GuildChannel,
+        overwrite: serenity::PermissionOverwrite,
+    ) -> Result<()> {
+        self.log(OverwriteAction::Create {
+            channel_id: channel.id,
+            overwrite,
+        })
+        .await
+    }
+
+    async fn delete(
+        &self,
+        channel: &serenity::GuildChannel,
+        kind: serenity::PermissionOverwriteType,
+    ) -> Result<()> {
+        self.log(OverwriteAction::Delete {
+            channel_id: channel.id,
+            kind,
+        })
+        .await
+    }
+
+    async fn disconnect(&self, guild_id: serenity::GuildId, user: u64) -> Result<()> {
+        self.log(OverwriteAction::Disconnect { guild_id, user })
+            .await
+    }
+}
+
+/// keeps every action in memory, to run the exclusion engine without a guild
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecordedOverwrites {
+    actions: Mutex<Vec<OverwriteAction>>,
+}
+
+#[cfg(test)]
+impl RecordedOverwrites {
+    pub fn actions(&self) -> Vec<OverwriteAction> {
+        self.actions.lock().unwrap().clone()
+    }
+
+    fn record(&self, action: OverwriteAction) {
+        self.actions.lock().unwrap().push(action);
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl Overwrites for RecordedOverwrites {
+    async fn create(
+        &self,
+        channel: &serenity::GuildChannel,
+        overwrite: serenity::PermissionOverwrite,
+    ) -> Result<()> {
+        self.record(OverwriteAction::Create {
+            channel_id: channel.id,
+            overwrite,
+        });
+        Ok(())
+    }
+
+    async fn delete(
+        &self,
+        channel: &serenity::GuildChannel,
+        kind: serenity::PermissionOverwriteType,
+    ) -> Result<()> {
+        self.record(OverwriteAction::Delete {
+            channel_id: channel.id,
+            kind,
+        });
+        Ok(())
+    }
+
+    async fn disconnect(&self, guild_id: serenity::GuildId, user: u64) -> Result<()> {
+        self.record(OverwriteAction::Disconnect { guild_id, user });
+        Ok(())
+    }
+}
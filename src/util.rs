@@ -1,9 +1,16 @@
-use crate::{error::Error, Data, Result};
+use crate::{
+    error::Error,
+    overwrites::{guild_overwrites, Overwrites},
+    Data, Result,
+};
 use apex_rs::model::Map;
-use chrono::{DateTime, Datelike, Utc};
-use mensa_swfr_rs::mensa;
+use chrono::{DateTime, TimeZone, Utc};
 use poise::serenity_prelude::{self as serenity, CacheHttp, CreateEmbed};
-use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use tokio::sync::RwLock;
 
 pub fn type_of<T>() -> &'static str {
     std::any::type_name::<T>()
@@ -47,94 +54,583 @@ pub fn to_short_timestamp(date: DateTime<Utc>) -> String {
     format!("<t:{}:t>", unix_time)
 }
 
-const SWFR_LOGO: &'static str = "https://cloud.nirusu.codes/s/McBDNYTkNjoEFyc/preview";
+/// where an exclusion comes from, overwrites are recorded per source
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExclusionSource {
+    Pair(i64),
+    Group(i64),
+}
 
-pub fn create_mensa_plan_by_day(day: &mensa::Day) -> CreateEmbed {
-    let mut embed = CreateEmbed::default();
-    embed
-        .title(format!(
-            "{} ({})",
-            &day.weekday()
-                .unwrap_or(chrono::Utc::now().weekday().into())
-                .full_name(),
-            day.to_chrono().unwrap().format("%d.%m.%Y")
-        ))
-        .color(crate::color())
-        .thumbnail(SWFR_LOGO);
-    for menu in day.menues.iter() {
-        let price = &menu.price;
-        embed.field(
-            &menu.art,
-            format!(
-                "{}\n\nZusatz: {}\n\nPreis: {}/{}/{}",
-                Regex::new(r"--+").unwrap().replace(&menu.name, "\n\n!!! "),
-                match &menu.food_type {
-                    Some(typ) => typ,
-                    None => "None",
-                },
-                price.price_students,
-                price.price_workers,
-                price.price_guests,
-            ),
-            false,
-        );
+impl ExclusionSource {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Pair(_) => "pair",
+            Self::Group(_) => "group",
+        }
+    }
+
+    pub fn id(&self) -> i64 {
+        match self {
+            Self::Pair(id) | Self::Group(id) => *id,
+        }
+    }
+
+    fn from_record(kind: &str, id: i64) -> Self {
+        match kind {
+            "group" => Self::Group(id),
+            _ => Self::Pair(id),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExclusionDirection {
+    /// both users can't see each other
+    Mutual,
+    /// only the user loses visibility of channels where the other one is active
+    OneWay(u64),
+}
+
+impl From<Option<i64>> for ExclusionDirection {
+    fn from(ignoring_user: Option<i64>) -> ExclusionDirection {
+        match ignoring_user {
+            Some(user) => ExclusionDirection::OneWay(user as u64),
+            None => ExclusionDirection::Mutual,
+        }
+    }
+}
+
+impl From<ExclusionDirection> for Option<i64> {
+    fn from(direction: ExclusionDirection) -> Option<i64> {
+        match direction {
+            ExclusionDirection::OneWay(user) => Some(user as i64),
+            ExclusionDirection::Mutual => None,
+        }
     }
-    embed
 }
 
-pub struct Exclusion((u64, u64));
+#[derive(Clone)]
+pub struct Exclusion {
+    id: Option<i64>,
+    group: Option<i64>,
+    users: (u64, u64),
+    direction: ExclusionDirection,
+    reason: Option<String>,
+    expires_at: Option<DateTime<Utc>>,
+}
 
 impl Exclusion {
+    /// the id of the exclusion, `None` if it isn't stored yet or comes from a group
+    pub fn id(&self) -> Option<i64> {
+        self.id
+    }
+
+    pub fn source(&self) -> Option<ExclusionSource> {
+        match (self.id, self.group) {
+            (_, Some(group)) => Some(ExclusionSource::Group(group)),
+            (Some(id), None) => Some(ExclusionSource::Pair(id)),
+            (None, None) => None,
+        }
+    }
+
     pub fn users(&self) -> &(u64, u64) {
-        &self.0
+        &self.users
+    }
+
+    pub fn direction(&self) -> ExclusionDirection {
+        self.direction
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    pub fn expires_at(&self) -> Option<&DateTime<Utc>> {
+        self.expires_at.as_ref()
+    }
+
+    pub fn with_reason(mut self, reason: Option<String>) -> Self {
+        self.reason = reason;
+        self
+    }
+
+    pub fn with_expiry(mut self, expires_at: Option<DateTime<Utc>>) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+
+    pub fn with_direction(mut self, direction: ExclusionDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// the other user of this exclusion if `user` is part of it
+    pub fn partner(&self, user: u64) -> Option<u64> {
+        match self.users {
+            (user_1, user_2) if user_1 == user => Some(user_2),
+            (user_1, user_2) if user_2 == user => Some(user_1),
+            _ => None,
+        }
+    }
+
+    /// the user that `user` can't see, if `user` loses visibility because of this exclusion
+    pub fn ignores(&self, user: u64) -> Option<u64> {
+        match self.direction {
+            ExclusionDirection::Mutual => self.partner(user),
+            ExclusionDirection::OneWay(ignoring) if ignoring == user => self.partner(user),
+            ExclusionDirection::OneWay(_) => None,
+        }
+    }
+
+    /// the user that has to be hidden from a channel with the present users
+    pub fn hidden_user(&self, present: &[u64]) -> Option<u64> {
+        let (user_1, user_2) = self.users;
+        match self.direction {
+            ExclusionDirection::Mutual if present.contains(&user_1) => Some(user_2),
+            ExclusionDirection::Mutual if present.contains(&user_2) => Some(user_1),
+            ExclusionDirection::Mutual => None,
+            ExclusionDirection::OneWay(ignoring) => self
+                .partner(ignoring)
+                .filter(|ignored| present.contains(ignored))
+                .map(|_| ignoring),
+        }
     }
 }
 
 impl From<(i64, i64)> for Exclusion {
     fn from(tuple: (i64, i64)) -> Exclusion {
-        Exclusion {
-            0: (tuple.0 as u64, tuple.1 as u64),
-        }
+        (tuple.0 as u64, tuple.1 as u64).into()
     }
 }
 impl From<(u64, u64)> for Exclusion {
+    /// the users are ordered, so (a, b) and (b, a) are the same exclusion
     fn from(tuple: (u64, u64)) -> Exclusion {
         Exclusion {
-            0: (tuple.0, tuple.1),
+            id: None,
+            group: None,
+            users: (tuple.0.min(tuple.1), tuple.0.max(tuple.1)),
+            direction: ExclusionDirection::Mutual,
+            reason: None,
+            expires_at: None,
         }
     }
 }
 
+pub fn from_timestamp(timestamp: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .unwrap_or_else(Utc::now)
+}
+
 pub async fn get_user_exclusions(
     database: &sqlx::SqlitePool,
     guild_id: i64,
 ) -> Result<Vec<Exclusion>> {
-    Ok(
-        sqlx::query!("SELECT * FROM exclusions WHERE guild_id = ?", guild_id)
-            .fetch_all(database)
-            .await?
-            .iter()
-            .map(|record| (record.user_1, record.user_2).into())
-            .collect(),
+    Ok(sqlx::query!(
+        "SELECT * FROM exclusions WHERE guild_id = ? ORDER BY id",
+        guild_id
+    )
+    .fetch_all(database)
+    .await?
+    .into_iter()
+    .map(|record| Exclusion {
+        id: Some(record.id),
+        direction: record.ignoring_user.into(),
+        reason: record.reason,
+        expires_at: record.expires_at.map(from_timestamp),
+        ..Exclusion::from((record.user_1, record.user_2))
+    })
+    .collect())
+}
+
+pub async fn get_user_exclusion_by_id(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    id: i64,
+) -> Result<Option<Exclusion>> {
+    Ok(sqlx::query!(
+        "SELECT * FROM exclusions WHERE guild_id = ? AND id = ?",
+        guild_id,
+        id
+    )
+    .fetch_optional(database)
+    .await?
+    .map(|record| Exclusion {
+        id: Some(record.id),
+        direction: record.ignoring_user.into(),
+        reason: record.reason,
+        expires_at: record.expires_at.map(from_timestamp),
+        ..Exclusion::from((record.user_1, record.user_2))
+    }))
+}
+
+pub async fn get_user_exclusion_by_users(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    users: (u64, u64),
+) -> Result<Option<Exclusion>> {
+    let exclusion = Exclusion::from(users);
+    let user_1 = exclusion.users.0 as i64;
+    let user_2 = exclusion.users.1 as i64;
+    Ok(sqlx::query!(
+        "SELECT * FROM exclusions WHERE guild_id = ? AND user_1 = ? AND user_2 = ?",
+        guild_id,
+        user_1,
+        user_2
+    )
+    .fetch_optional(database)
+    .await?
+    .map(|record| Exclusion {
+        id: Some(record.id),
+        direction: record.ignoring_user.into(),
+        reason: record.reason,
+        expires_at: record.expires_at.map(from_timestamp),
+        ..exclusion
+    }))
+}
+
+/// all exclusions of every guild that expired until now
+pub async fn get_expired_user_exclusions(
+    database: &sqlx::SqlitePool,
+) -> Result<Vec<(i64, Exclusion)>> {
+    let now = Utc::now().timestamp();
+    Ok(sqlx::query!(
+        "SELECT * FROM exclusions WHERE expires_at IS NOT NULL AND expires_at <= ?",
+        now
+    )
+    .fetch_all(database)
+    .await?
+    .into_iter()
+    .map(|record| {
+        (
+            record.guild_id,
+            Exclusion {
+                id: Some(record.id),
+                direction: record.ignoring_user.into(),
+                reason: record.reason,
+                expires_at: record.expires_at.map(from_timestamp),
+                ..Exclusion::from((record.user_1, record.user_2))
+            },
+        )
+    })
+    .collect())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GroupMember {
+    User(u64),
+    Role(u64),
+}
+
+impl GroupMember {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::User(_) => "user",
+            Self::Role(_) => "role",
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        match self {
+            Self::User(id) | Self::Role(id) => *id,
+        }
+    }
+
+    pub fn mention(&self) -> String {
+        match self {
+            Self::User(id) => format!("<@{}>", id),
+            Self::Role(id) => format!("<@&{}>", id),
+        }
+    }
+
+    fn from_record(kind: &str, id: i64) -> Self {
+        match kind {
+            "role" => Self::Role(id as u64),
+            _ => Self::User(id as u64),
+        }
+    }
+
+    /// all users of the guild this member stands for
+    fn users(&self, guild: Option<&serenity::Guild>) -> Vec<u64> {
+        match self {
+            Self::User(id) => vec![*id],
+            Self::Role(role) => guild.map_or(Vec::new(), |guild| {
+                guild
+                    .members
+                    .values()
+                    .filter(|member| member.roles.contains(&serenity::RoleId(*role)))
+                    .map(|member| member.user.id.0)
+                    .collect()
+            }),
+        }
+    }
+}
+
+pub struct ExclusionGroup {
+    pub id: i64,
+    pub name: String,
+    pub members: Vec<GroupMember>,
+}
+
+impl ExclusionGroup {
+    /// every member of the group is excluded from every other member.
+    /// users that share a role of the group are not excluded from each other
+    pub fn exclusions(&self, guild: Option<&serenity::Guild>) -> Vec<Exclusion> {
+        let mut exclusions = Vec::new();
+        for (index, member_1) in self.members.iter().enumerate() {
+            for member_2 in self.members.iter().skip(index + 1) {
+                for user_1 in member_1.users(guild) {
+                    for user_2 in member_2.users(guild) {
+                        if user_1 != user_2 {
+                            exclusions.push(Exclusion {
+                                group: Some(self.id),
+                                ..Exclusion::from((user_1, user_2))
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        exclusions
+    }
+}
+
+pub async fn get_exclusion_groups(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+) -> Result<Vec<ExclusionGroup>> {
+    let mut groups = sqlx::query!(
+        "SELECT id, name FROM exclusion_groups WHERE guild_id = ? ORDER BY name",
+        guild_id
+    )
+    .fetch_all(database)
+    .await?
+    .into_iter()
+    .map(|record| ExclusionGroup {
+        id: record.id,
+        name: record.name,
+        members: Vec::new(),
+    })
+    .collect::<Vec<ExclusionGroup>>();
+    let members = sqlx::query!(
+        "SELECT exclusion_group_members.* FROM exclusion_group_members
+        JOIN exclusion_groups ON exclusion_groups.id = exclusion_group_members.group_id
+        WHERE exclusion_groups.guild_id = ?",
+        guild_id
+    )
+    .fetch_all(database)
+    .await?;
+    for record in members {
+        let member = GroupMember::from_record(&record.kind, record.member_id);
+        if let Some(group) = groups.iter_mut().find(|group| group.id == record.group_id) {
+            group.members.push(member);
+        }
+    }
+    Ok(groups)
+}
+
+pub async fn get_exclusion_group_id(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    name: &str,
+) -> Result<i64> {
+    Ok(sqlx::query!(
+        "SELECT id FROM exclusion_groups WHERE guild_id = ? AND name = ?",
+        guild_id,
+        name
+    )
+    .fetch_optional(database)
+    .await?
+    .ok_or_else(|| Error::InvalidInput("group doesn't exist"))?
+    .id)
+}
+
+pub async fn create_exclusion_group(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    name: &str,
+) -> Result<i64> {
+    let result = sqlx::query!(
+        "INSERT OR IGNORE INTO exclusion_groups (guild_id, name) VALUES (?, ?)",
+        guild_id,
+        name
+    )
+    .execute(database)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(Box::new(Error::InvalidInput("group already exists")));
+    }
+    Ok(result.last_insert_rowid())
+}
+
+pub async fn delete_exclusion_group(database: &sqlx::SqlitePool, group_id: i64) -> Result<()> {
+    sqlx::query!(
+        "DELETE FROM exclusion_group_members WHERE group_id = ?",
+        group_id
+    )
+    .execute(database)
+    .await?;
+    sqlx::query!("DELETE FROM exclusion_groups WHERE id = ?", group_id)
+        .execute(database)
+        .await?;
+    Ok(())
+}
+
+pub async fn add_exclusion_group_member(
+    database: &sqlx::SqlitePool,
+    group_id: i64,
+    member: GroupMember,
+) -> Result<()> {
+    let member_id = member.id() as i64;
+    let kind = member.kind();
+    let result = sqlx::query!(
+        "INSERT OR IGNORE INTO exclusion_group_members (group_id, member_id, kind) VALUES (?, ?, ?)",
+        group_id,
+        member_id,
+        kind
+    )
+    .execute(database)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(Box::new(Error::InvalidInput(
+            "already a member of the group",
+        )));
+    }
+    Ok(())
+}
+
+pub async fn remove_exclusion_group_member(
+    database: &sqlx::SqlitePool,
+    group_id: i64,
+    member: GroupMember,
+) -> Result<()> {
+    let member_id = member.id() as i64;
+    let result = sqlx::query!(
+        "DELETE FROM exclusion_group_members WHERE group_id = ? AND member_id = ?",
+        group_id,
+        member_id
+    )
+    .execute(database)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(Box::new(Error::InvalidInput("not a member of the group")));
+    }
+    Ok(())
+}
+
+/// if one of the roles is a member of an exclusion group of the guild
+pub async fn is_exclusion_group_role(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    roles: &[serenity::RoleId],
+) -> Result<bool> {
+    Ok(sqlx::query!(
+        "SELECT exclusion_group_members.member_id FROM exclusion_group_members
+        JOIN exclusion_groups ON exclusion_groups.id = exclusion_group_members.group_id
+        WHERE exclusion_groups.guild_id = ? AND exclusion_group_members.kind = 'role'",
+        guild_id
     )
+    .fetch_all(database)
+    .await?
+    .iter()
+    .any(|record| roles.contains(&serenity::RoleId(record.member_id as u64))))
+}
+
+/// all exclusions of the guild, pairs and the ones resulting from groups
+pub async fn get_guild_exclusions(
+    ctx: &serenity::Context,
+    database: &sqlx::SqlitePool,
+    guild_id: serenity::GuildId,
+) -> Result<Vec<Exclusion>> {
+    let mut exclusions = get_user_exclusions(database, guild_id.into()).await?;
+    let guild = ctx.cache.guild(guild_id);
+    for group in get_exclusion_groups(database, guild_id.into()).await? {
+        exclusions.extend(group.exclusions(guild.as_ref()));
+    }
+    Ok(exclusions)
 }
 
+/// stores the exclusion and returns its id
 pub async fn add_user_exclusion(
     database: &sqlx::SqlitePool,
     guild_id: i64,
     exclusion: &Exclusion,
-) -> Result<()> {
-    let user_1 = exclusion.0 .0 as i64;
-    let user_2 = exclusion.0 .1 as i64;
-    sqlx::query!(
-        "INSERT INTO exclusions (guild_id, user_1, user_2) VALUES (?, ?, ?)",
+) -> Result<i64> {
+    let user_1 = exclusion.users.0 as i64;
+    let user_2 = exclusion.users.1 as i64;
+    if user_1 == user_2 {
+        return Err(Box::new(Error::InvalidInput(
+            "users can't exclude themselves",
+        )));
+    }
+    let reason = exclusion.reason();
+    let expires_at = exclusion.expires_at.map(|date| date.timestamp());
+    let ignoring_user: Option<i64> = exclusion.direction.into();
+    let result = sqlx::query!(
+        "INSERT OR IGNORE INTO exclusions (guild_id, user_1, user_2, ignoring_user, reason, expires_at) VALUES (?, ?, ?, ?, ?, ?)",
         guild_id,
         user_1,
         user_2,
+        ignoring_user,
+        reason,
+        expires_at,
     )
     .execute(database)
     .await?;
-    Ok(())
+    if result.rows_affected() == 0 {
+        return Err(Box::new(Error::InvalidInput("users are already excluded")));
+    }
+    Ok(result.last_insert_rowid())
+}
+
+/// stores all exclusions in one transaction and returns how many were added
+pub async fn import_user_exclusions(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    actor: u64,
+    exclusions: &[Exclusion],
+) -> Result<u64> {
+    let actor = actor as i64;
+    let created_at = Utc::now().timestamp();
+    let mut imported = 0;
+    let mut transaction = database.begin().await?;
+    for exclusion in exclusions {
+        let user_1 = exclusion.users.0 as i64;
+        let user_2 = exclusion.users.1 as i64;
+        let ignoring_user: Option<i64> = exclusion.direction.into();
+        let reason = exclusion.reason();
+        let expires_at = exclusion.expires_at.map(|date| date.timestamp());
+        let inserted = sqlx::query!(
+            "INSERT OR IGNORE INTO exclusions (guild_id, user_1, user_2, ignoring_user, reason, expires_at) VALUES (?, ?, ?, ?, ?, ?)",
+            guild_id,
+            user_1,
+            user_2,
+            ignoring_user,
+            reason,
+            expires_at,
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+        // the pair was added since the preview
+        if inserted == 0 {
+            continue;
+        }
+        imported += inserted;
+        sqlx::query!(
+            "INSERT INTO exclusion_audit (guild_id, actor_id, action, user_1, user_2, reason, created_at) VALUES (?, ?, 'import', ?, ?, ?, ?)",
+            guild_id,
+            actor,
+            user_1,
+            user_2,
+            reason,
+            created_at,
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+    transaction.commit().await?;
+    Ok(imported)
 }
 
 pub async fn remove_user_exclusion(
@@ -142,8 +638,8 @@ pub async fn remove_user_exclusion(
     guild_id: i64,
     exclusion: &Exclusion,
 ) -> Result<()> {
-    let user_1 = exclusion.0 .0 as i64;
-    let user_2 = exclusion.0 .1 as i64;
+    let user_1 = exclusion.users.0 as i64;
+    let user_2 = exclusion.users.1 as i64;
     sqlx::query!(
         "DELETE FROM exclusions WHERE guild_id = ? AND user_1 = ? AND user_2 = ?",
         guild_id,
@@ -155,59 +651,1462 @@ pub async fn remove_user_exclusion(
     Ok(())
 }
 
-pub async fn check_for_exclusion_collision(
+/// revokes the overwrites of the exclusion, removes it and writes it to the audit log
+pub async fn lift_user_exclusion(
     ctx: &serenity::Context,
-    channel: serenity::Channel,
-    data: &Data,
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    exclusion: &Exclusion,
+    actor: u64,
+    action: &str,
+    reason: Option<&str>,
 ) -> Result<()> {
-    let guild_channel = channel
-        .guild()
-        .map_or(Err(Error::InvalidInput("Not in a guild")), |channel| {
-            Ok(channel)
-        })?;
-    let guild_id = guild_channel.guild_id;
-    let exclusions = get_user_exclusions(&data.database, guild_id.into()).await?;
-    let users = guild_channel
-        .members(
-            ctx.cache()
-                .map_or(Err(Error::InvalidInput("Cache unavailable")), |cache| {
-                    Ok(cache)
-                })?,
-        )
-        .await?
-        .iter()
-        .map(|member| member.user.id.0)
-        .collect::<Vec<u64>>();
-    for exclusion in exclusions {
-        let (user_1, user_2) = exclusion.users();
-        if users.contains(user_1) {
-            exclude_user_from_channel(ctx, &guild_channel, *user_2).await?;
-        } else if users.contains(user_2) {
-            exclude_user_from_channel(ctx, &guild_channel, *user_1).await?;
+    revoke_exclusion_overwrites(ctx, database, guild_id, exclusion).await?;
+    remove_user_exclusion(database, guild_id, exclusion).await?;
+    add_exclusion_audit(database, guild_id, actor, action, exclusion, reason).await?;
+    Ok(())
+}
+
+/// lifts all expired exclusions every minute and releases the text channels of the guilds
+/// whose exclusions expired or where users left the activity window since the last minute
+pub async fn lift_expired_exclusions(
+    ctx: serenity::Context,
+    database: sqlx::SqlitePool,
+    policies: Arc<GuildPolicies>,
+) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+    let mut last_tick = Utc::now().timestamp();
+    loop {
+        interval.tick().await;
+        let now = Utc::now().timestamp();
+        let expired = match get_expired_user_exclusions(&database).await {
+            Ok(expired) => expired,
+            Err(why) => {
+                tracing::error!("couldn't get expired exclusions: {}", why);
+                continue;
+            }
+        };
+        let bot = ctx.cache.current_user_id().0;
+        let mut changed = HashSet::new();
+        for (guild_id, exclusion) in expired {
+            if let Err(why) =
+                lift_user_exclusion(&ctx, &database, guild_id, &exclusion, bot, "expire", None)
+                    .await
+            {
+                tracing::error!("couldn't lift expired exclusion: {}", why);
+            }
+            let guild_id = serenity::GuildId(guild_id as u64);
+            policies.invalidate(guild_id).await;
+            changed.insert(guild_id);
+        }
+        match get_lapsed_activity_guilds(&database, last_tick, now).await {
+            Ok(lapsed) => changed.extend(lapsed),
+            Err(why) => tracing::error!("couldn't get guilds with lapsed activity: {}", why),
+        }
+        last_tick = now;
+        for guild_id in changed {
+            if let Err(why) = release_text_channels(&ctx, &database, &policies, guild_id).await {
+                tracing::error!("couldn't release text channels of {}: {}", guild_id, why);
+            }
         }
     }
+}
+
+/// guilds with the activity policy where a message in a channel with exclusion overwrites
+/// left the activity window after `since` and until `until`
+async fn get_lapsed_activity_guilds(
+    database: &sqlx::SqlitePool,
+    since: i64,
+    until: i64,
+) -> Result<Vec<serenity::GuildId>> {
+    Ok(sqlx::query!(
+        "SELECT DISTINCT exclusion_overwrites.guild_id FROM exclusion_overwrites
+        JOIN guild_settings ON guild_settings.guild_id = exclusion_overwrites.guild_id
+        JOIN channel_activity ON channel_activity.channel_id = exclusion_overwrites.channel_id
+        WHERE guild_settings.text_policy = 'activity'
+        AND channel_activity.last_message_at + guild_settings.activity_minutes * 60 > ?
+        AND channel_activity.last_message_at + guild_settings.activity_minutes * 60 <= ?",
+        since,
+        until
+    )
+    .fetch_all(database)
+    .await?
+    .iter()
+    .map(|record| serenity::GuildId(record.guild_id as u64))
+    .collect())
+}
+
+pub struct AuditEntry {
+    pub id: i64,
+    pub actor: u64,
+    pub action: String,
+    pub users: (u64, u64),
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// the name of the group and its changed member, if the entry is about an exclusion group
+    pub group: Option<(String, Option<GroupMember>)>,
+}
+
+/// writes a change of the exclusions to the audit log
+pub async fn add_exclusion_audit(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    actor: u64,
+    action: &str,
+    exclusion: &Exclusion,
+    reason: Option<&str>,
+) -> Result<()> {
+    let actor = actor as i64;
+    let user_1 = exclusion.users.0 as i64;
+    let user_2 = exclusion.users.1 as i64;
+    let created_at = Utc::now().timestamp();
+    sqlx::query!(
+        "INSERT INTO exclusion_audit (guild_id, actor_id, action, user_1, user_2, reason, created_at) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        guild_id,
+        actor,
+        action,
+        user_1,
+        user_2,
+        reason,
+        created_at,
+    )
+    .execute(database)
+    .await?;
     Ok(())
 }
 
-async fn exclude_user_from_channel(
-    ctx: &serenity::Context,
-    channel: &serenity::GuildChannel,
-    user: u64,
+/// writes a change of an exclusion group to the audit log
+pub async fn add_group_audit(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    actor: u64,
+    action: &str,
+    group: &str,
+    member: Option<GroupMember>,
 ) -> Result<()> {
-    let permission = if channel.is_text_based() {
-        serenity::Permissions::VIEW_CHANNEL
-    } else {
-        serenity::Permissions::CONNECT
-    };
-    channel
-        .create_permission(
-            ctx.http(),
-            &serenity::PermissionOverwrite {
-                allow: serenity::Permissions::empty(),
-                deny: permission,
-                kind: serenity::PermissionOverwriteType::Member(user.into()),
-            },
-        )
-        .await?;
+    let actor = actor as i64;
+    let member_id = member.map_or(0, |member| member.id() as i64);
+    let member_kind = member.map(|member| member.kind());
+    let created_at = Utc::now().timestamp();
+    sqlx::query!(
+        "INSERT INTO exclusion_audit (guild_id, actor_id, action, user_1, user_2, group_name, member_kind, created_at) VALUES (?, ?, ?, ?, 0, ?, ?, ?)",
+        guild_id,
+        actor,
+        action,
+        member_id,
+        group,
+        member_kind,
+        created_at,
+    )
+    .execute(database)
+    .await?;
     Ok(())
 }
+
+/// the audit log of a guild, newest entries first
+pub async fn get_exclusion_audit(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+) -> Result<Vec<AuditEntry>> {
+    Ok(sqlx::query!(
+        "SELECT * FROM exclusion_audit WHERE guild_id = ? ORDER BY id DESC",
+        guild_id
+    )
+    .fetch_all(database)
+    .await?
+    .into_iter()
+    .map(|record| AuditEntry {
+        id: record.id,
+        actor: record.actor_id as u64,
+        action: record.action,
+        users: (record.user_1 as u64, record.user_2 as u64),
+        reason: record.reason,
+        created_at: from_timestamp(record.created_at),
+        group: record.group_name.map(|name| {
+            let member = record
+                .member_kind
+                .map(|kind| GroupMember::from_record(&kind, record.user_1));
+            (name, member)
+        }),
+    })
+    .collect())
+}
+
+pub async fn get_moderator_role(database: &sqlx::SqlitePool, guild_id: i64) -> Result<Option<u64>> {
+    Ok(sqlx::query!(
+        "SELECT moderator_role FROM guild_settings WHERE guild_id = ?",
+        guild_id
+    )
+    .fetch_optional(database)
+    .await?
+    .and_then(|record| record.moderator_role)
+    .map(|role| role as u64))
+}
+
+pub async fn set_moderator_role(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    role: Option<u64>,
+) -> Result<()> {
+    let role = role.map(|role| role as i64);
+    sqlx::query!(
+        "INSERT INTO guild_settings (guild_id, moderator_role) VALUES (?, ?)
+        ON CONFLICT(guild_id) DO UPDATE SET moderator_role = excluded.moderator_role",
+        guild_id,
+        role,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// the channel simulated overwrites are logged to, `None` if the guild isn't simulated
+pub async fn get_simulate_channel(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+) -> Result<Option<u64>> {
+    Ok(sqlx::query!(
+        "SELECT simulate_channel FROM guild_settings WHERE guild_id = ?",
+        guild_id
+    )
+    .fetch_optional(database)
+    .await?
+    .and_then(|record| record.simulate_channel)
+    .map(|channel| channel as u64))
+}
+
+pub async fn set_simulate_channel(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    channel: Option<u64>,
+) -> Result<()> {
+    let channel = channel.map(|channel| channel as i64);
+    sqlx::query!(
+        "INSERT INTO guild_settings (guild_id, simulate_channel) VALUES (?, ?)
+        ON CONFLICT(guild_id) DO UPDATE SET simulate_channel = excluded.simulate_channel",
+        guild_id,
+        channel,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// how text channels are handled by the exclusions
+#[derive(Clone, Copy, PartialEq, Debug, poise::ChoiceParameter)]
+pub enum TextPolicy {
+    /// text channels are never changed
+    #[name = "ignore"]
+    Ignore,
+    /// users that wrote in the channel recently are present
+    #[name = "activity"]
+    Activity,
+    /// only opted in channels are used, everyone who can see the channel is present
+    #[name = "opt-in"]
+    OptIn,
+}
+
+impl TextPolicy {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Ignore => "ignore",
+            Self::Activity => "activity",
+            Self::OptIn => "opt-in",
+        }
+    }
+}
+
+impl From<&str> for TextPolicy {
+    fn from(kind: &str) -> TextPolicy {
+        match kind {
+            "activity" => TextPolicy::Activity,
+            "opt-in" => TextPolicy::OptIn,
+            _ => TextPolicy::Ignore,
+        }
+    }
+}
+
+/// the text policy of the guild and the window in minutes for the activity policy
+pub async fn get_text_policy(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+) -> Result<(TextPolicy, i64)> {
+    Ok(sqlx::query!(
+        "SELECT text_policy, activity_minutes FROM guild_settings WHERE guild_id = ?",
+        guild_id
+    )
+    .fetch_optional(database)
+    .await?
+    .map_or((TextPolicy::Ignore, 60), |record| {
+        (record.text_policy.as_str().into(), record.activity_minutes)
+    }))
+}
+
+pub async fn set_text_policy(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    policy: TextPolicy,
+    activity_minutes: i64,
+) -> Result<()> {
+    let policy = policy.kind();
+    sqlx::query!(
+        "INSERT INTO guild_settings (guild_id, text_policy, activity_minutes) VALUES (?, ?, ?)
+        ON CONFLICT(guild_id) DO UPDATE SET text_policy = excluded.text_policy, activity_minutes = excluded.activity_minutes",
+        guild_id,
+        policy,
+        activity_minutes,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// the text policy of a guild and if it has exclusions at all,
+/// cached because every message of the guild needs them
+#[derive(Clone, Copy)]
+pub struct GuildPolicy {
+    pub policy: TextPolicy,
+    pub activity_minutes: i64,
+    /// if the guild has an exclusion pair or a group member
+    pub active: bool,
+}
+
+/// the cached [`GuildPolicy`] of the guilds, an entry has to be invalidated when
+/// the text policy or the exclusions of its guild change
+#[derive(Default)]
+pub struct GuildPolicies {
+    policies: RwLock<HashMap<serenity::GuildId, GuildPolicy>>,
+}
+
+impl GuildPolicies {
+    pub async fn get(
+        &self,
+        database: &sqlx::SqlitePool,
+        guild_id: serenity::GuildId,
+    ) -> Result<GuildPolicy> {
+        if let Some(policy) = self.policies.read().await.get(&guild_id) {
+            return Ok(*policy);
+        }
+        let (policy, activity_minutes) = get_text_policy(database, guild_id.into()).await?;
+        let policy = GuildPolicy {
+            policy,
+            activity_minutes,
+            active: has_exclusions(database, guild_id.into()).await?,
+        };
+        self.policies.write().await.insert(guild_id, policy);
+        Ok(policy)
+    }
+
+    pub async fn invalidate(&self, guild_id: serenity::GuildId) {
+        self.policies.write().await.remove(&guild_id);
+    }
+}
+
+/// if the guild has an exclusion pair or a member in an exclusion group
+async fn has_exclusions(database: &sqlx::SqlitePool, guild_id: i64) -> Result<bool> {
+    Ok(sqlx::query!(
+        r#"SELECT EXISTS(SELECT 1 FROM exclusions WHERE guild_id = ?)
+        OR EXISTS(
+            SELECT 1 FROM exclusion_group_members
+            JOIN exclusion_groups ON exclusion_groups.id = exclusion_group_members.group_id
+            WHERE exclusion_groups.guild_id = ?
+        ) AS "active!: bool""#,
+        guild_id,
+        guild_id
+    )
+    .fetch_one(database)
+    .await?
+    .active)
+}
+
+pub async fn set_exclusion_channel(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    channel_id: u64,
+    opt_in: bool,
+) -> Result<()> {
+    let channel_id = channel_id as i64;
+    if opt_in {
+        sqlx::query!(
+            "INSERT OR IGNORE INTO exclusion_channels (channel_id, guild_id) VALUES (?, ?)",
+            channel_id,
+            guild_id
+        )
+        .execute(database)
+        .await?;
+    } else {
+        sqlx::query!(
+            "DELETE FROM exclusion_channels WHERE channel_id = ?",
+            channel_id
+        )
+        .execute(database)
+        .await?;
+    }
+    Ok(())
+}
+
+/// remembers that the author wrote in the channel
+pub async fn record_channel_activity(
+    database: &sqlx::SqlitePool,
+    message: &serenity::Message,
+) -> Result<()> {
+    let channel_id: i64 = message.channel_id.into();
+    let user_id: i64 = message.author.id.into();
+    let last_message_at = message.timestamp.unix_timestamp();
+    sqlx::query!(
+        "INSERT INTO channel_activity (channel_id, user_id, last_message_at) VALUES (?, ?, ?)
+        ON CONFLICT(channel_id, user_id) DO UPDATE SET last_message_at = excluded.last_message_at",
+        channel_id,
+        user_id,
+        last_message_at,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// records the activity of the message and checks the channel if the guild uses the activity
+/// policy and has exclusions
+pub async fn check_message_for_exclusion(
+    ctx: &serenity::Context,
+    message: &serenity::Message,
+    data: &Data,
+) -> Result<()> {
+    let guild_id = match message.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+    let policy = data.text_policies.get(&data.database, guild_id).await?;
+    if policy.policy != TextPolicy::Activity || !policy.active {
+        return Ok(());
+    }
+    record_channel_activity(&data.database, message).await?;
+    let channel = message.channel_id.to_channel(ctx).await?;
+    check_for_exclusion_collision(ctx, channel, data).await
+}
+
+pub async fn check_for_exclusion_collision(
+    ctx: &serenity::Context,
+    channel: serenity::Channel,
+    data: &Data,
+) -> Result<()> {
+    let guild_channel = channel
+        .guild()
+        .map_or(Err(Error::InvalidInput("Not in a guild")), |channel| {
+            Ok(channel)
+        })?;
+    let users =
+        match channel_presence(ctx, &data.database, &data.text_policies, &guild_channel).await? {
+            Some(users) => users,
+            None => return Ok(()),
+        };
+    let guild_id = guild_channel.guild_id;
+    let exclusions = get_guild_exclusions(ctx, &data.database, guild_id).await?;
+    let records = get_overwrite_records(&data.database, guild_id.into()).await?;
+    let overwrites = guild_overwrites(ctx, &data.database, guild_id.into()).await?;
+    let denied = deny_hidden_users(
+        overwrites.as_ref(),
+        &guild_channel,
+        &users,
+        &exclusions,
+        &records,
+    )
+    .await?;
+    // simulated overwrites don't exist, so they are not recorded
+    if overwrites.applies() {
+        for (user, source, allowed) in denied {
+            record_overwrite(&data.database, &guild_channel, user, source, allowed).await?;
+        }
+    }
+    Ok(())
+}
+
+/// denies the users the exclusions hide from the channel with the present `users`.
+/// reads neither the cache nor the database, `records` are the recorded overwrites of the guild.
+/// returns the denied users with the source of their exclusion and if the permission was
+/// allowed before, to record them
+pub async fn deny_hidden_users(
+    overwrites: &dyn Overwrites,
+    channel: &serenity::GuildChannel,
+    users: &[u64],
+    exclusions: &[Exclusion],
+    records: &[OverwriteRecord],
+) -> Result<Vec<(u64, ExclusionSource, bool)>> {
+    let permission = exclusion_permission(channel);
+    // both are updated with every denial, for users that are hidden by several exclusions
+    let mut channel = channel.clone();
+    let mut records = records.to_vec();
+    let mut denied = Vec::new();
+    for exclusion in exclusions {
+        let (user, source) = match (exclusion.hidden_user(users), exclusion.source()) {
+            (Some(user), Some(source)) => (user, source),
+            _ => continue,
+        };
+        let allowed = match deny_user(overwrites, &channel, user, source, &records).await? {
+            Some(allowed) => allowed,
+            None => continue,
+        };
+        let kind = serenity::PermissionOverwriteType::Member(user.into());
+        match channel
+            .permission_overwrites
+            .iter_mut()
+            .find(|overwrite| overwrite.kind == kind)
+        {
+            Some(overwrite) => {
+                overwrite.allow.remove(permission);
+                overwrite.deny.insert(permission);
+            }
+            None => channel
+                .permission_overwrites
+                .push(serenity::PermissionOverwrite {
+                    allow: serenity::Permissions::empty(),
+                    deny: permission,
+                    kind,
+                }),
+        }
+        records.push(OverwriteRecord {
+            source,
+            channel_id: channel.id.0,
+            user,
+            permission,
+            allowed,
+        });
+        denied.push((user, source, allowed));
+    }
+    Ok(denied)
+}
+
+/// checks all text and voice channels of a guild for exclusion collisions
+pub async fn check_guild_for_exclusion_collision(
+    ctx: &serenity::Context,
+    guild_id: serenity::GuildId,
+    data: &Data,
+) -> Result<()> {
+    for (_, channel) in guild_id.channels(ctx.http()).await? {
+        if !is_excludable(&channel) {
+            continue;
+        }
+        check_for_exclusion_collision(ctx, serenity::Channel::Guild(channel), data).await?;
+    }
+    Ok(())
+}
+
+/// checks if the user of the voice state joined a channel with someone they are excluded from.
+/// if that is the case the user (the later arrival) gets denied and disconnected.
+/// if someone in the channel ignores the user with a one way exclusion, they have to leave instead
+pub async fn check_voice_state_for_exclusion(
+    ctx: &serenity::Context,
+    voice_state: &serenity::VoiceState,
+    data: &Data,
+) -> Result<()> {
+    let (guild_id, channel_id) = match (voice_state.guild_id, voice_state.channel_id) {
+        (Some(guild_id), Some(channel_id)) => (guild_id, channel_id),
+        // user left voice or voice state is not from a guild
+        _ => return Ok(()),
+    };
+    let guild_channel = channel_id
+        .to_channel(ctx)
+        .await?
+        .guild()
+        .map_or(Err(Error::InvalidInput("Not in a guild")), |channel| {
+            Ok(channel)
+        })?;
+    let user = voice_state.user_id.0;
+    // the cache already contains the new voice state, so we ignore the user itself
+    let users = channel_members(ctx, &guild_channel)
+        .await?
+        .into_iter()
+        .filter(|id| *id != user)
+        .collect::<Vec<u64>>();
+    let exclusions = get_guild_exclusions(ctx, &data.database, guild_id).await?;
+    let collision = exclusions.iter().find_map(|exclusion| {
+        // the user ignores someone in the channel
+        if exclusion
+            .ignores(user)
+            .map_or(false, |ignored| users.contains(&ignored))
+        {
+            return Some((user, exclusion));
+        }
+        // someone in the channel ignores the user
+        users
+            .iter()
+            .find(|present| exclusion.ignores(**present) == Some(user))
+            .map(|present| (*present, exclusion))
+    });
+    if let Some((user, exclusion)) = collision {
+        let overwrites = guild_overwrites(ctx, &data.database, guild_id.into()).await?;
+        exclude_user_from_channel(overwrites.as_ref(), data, &guild_channel, user, exclusion)
+            .await?;
+        overwrites.disconnect(guild_id, user).await?;
+    }
+    Ok(())
+}
+
+/// ids of the users that are present in the channel, `None` if the channel is ignored.
+/// in voice channels everyone connected is present, text channels depend on the text policy
+async fn channel_presence(
+    ctx: &serenity::Context,
+    database: &sqlx::SqlitePool,
+    policies: &GuildPolicies,
+    channel: &serenity::GuildChannel,
+) -> Result<Option<Vec<u64>>> {
+    if !is_excludable(channel) {
+        return Ok(None);
+    }
+    if !channel.is_text_based() {
+        return Ok(Some(channel_members(ctx, channel).await?));
+    }
+    let channel_id: i64 = channel.id.into();
+    let policy = policies.get(database, channel.guild_id).await?;
+    match policy.policy {
+        TextPolicy::Ignore => Ok(None),
+        TextPolicy::Activity => {
+            let since =
+                (Utc::now() - chrono::Duration::minutes(policy.activity_minutes)).timestamp();
+            Ok(Some(
+                sqlx::query!(
+                    "SELECT user_id FROM channel_activity WHERE channel_id = ? AND last_message_at >= ?",
+                    channel_id,
+                    since
+                )
+                .fetch_all(database)
+                .await?
+                .iter()
+                .map(|record| record.user_id as u64)
+                .collect(),
+            ))
+        }
+        TextPolicy::OptIn => {
+            let opted_in = sqlx::query!(
+                "SELECT channel_id FROM exclusion_channels WHERE channel_id = ?",
+                channel_id
+            )
+            .fetch_optional(database)
+            .await?
+            .is_some();
+            if opted_in {
+                Ok(Some(channel_members(ctx, channel).await?))
+            } else {
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// ids of all members in the channel
+async fn channel_members(
+    ctx: &serenity::Context,
+    channel: &serenity::GuildChannel,
+) -> Result<Vec<u64>> {
+    Ok(channel
+        .members(
+            ctx.cache()
+                .map_or(Err(Error::InvalidInput("Cache unavailable")), |cache| {
+                    Ok(cache)
+                })?,
+        )
+        .await?
+        .iter()
+        .map(|member| member.user.id.0)
+        .collect())
+}
+
+/// a difference between the overwrites of a guild and its exclusions
+pub enum ExclusionChange {
+    /// the user has to be denied in the channel because of the exclusion
+    Deny {
+        channel: serenity::GuildChannel,
+        user: u64,
+        exclusion: Exclusion,
+    },
+    /// the overwrite was recorded for an exclusion that doesn't exist or doesn't hide the user
+    /// anymore
+    Revoke {
+        channel_id: u64,
+        user: u64,
+        source: ExclusionSource,
+        permission: serenity::Permissions,
+    },
+}
+
+impl ExclusionChange {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Deny { channel, user, .. } => format!("deny <@{}> in <#{}>", user, channel.id),
+            Self::Revoke {
+                channel_id, user, ..
+            } => format!("allow <@{}> in <#{}>", user, channel_id),
+        }
+    }
+}
+
+/// an overwrite the bot applied because of an exclusion
+#[derive(Clone)]
+pub struct OverwriteRecord {
+    pub source: ExclusionSource,
+    pub channel_id: u64,
+    pub user: u64,
+    pub permission: serenity::Permissions,
+    /// if the overwrite allowed the permission before
+    pub allowed: bool,
+}
+
+impl OverwriteRecord {
+    fn revoke(&self) -> ExclusionChange {
+        ExclusionChange::Revoke {
+            channel_id: self.channel_id,
+            user: self.user,
+            source: self.source,
+            permission: self.permission,
+        }
+    }
+}
+
+async fn get_overwrite_records(
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+) -> Result<Vec<OverwriteRecord>> {
+    Ok(sqlx::query!(
+        "SELECT source, source_id, channel_id, user_id, permission, allowed FROM exclusion_overwrites WHERE guild_id = ?",
+        guild_id
+    )
+    .fetch_all(database)
+    .await?
+    .iter()
+    .map(|record| OverwriteRecord {
+        source: ExclusionSource::from_record(&record.source, record.source_id),
+        channel_id: record.channel_id as u64,
+        user: record.user_id as u64,
+        permission: serenity::Permissions::from_bits_truncate(record.permission as u64),
+        allowed: record.allowed != 0,
+    })
+    .collect())
+}
+
+/// revokes the recorded overwrites of the text channel whose users aren't hidden anymore,
+/// because their activity passed or the text policy doesn't use the channel.
+/// `users` are the present users, `None` if the channel is ignored.
+/// records of removed exclusions are left to the caller
+fn text_channel_revokes(
+    channel_id: u64,
+    users: Option<&[u64]>,
+    exclusions: &[Exclusion],
+    records: &[OverwriteRecord],
+) -> Vec<ExclusionChange> {
+    records
+        .iter()
+        .filter(|record| record.channel_id == channel_id)
+        .filter(|record| {
+            let mut sources = exclusions
+                .iter()
+                .filter(|exclusion| {
+                    exclusion.source() == Some(record.source)
+                        && exclusion.ignores(record.user).is_some()
+                })
+                .peekable();
+            sources.peek().is_some()
+                && !sources.any(|exclusion| {
+                    users.map_or(false, |users| {
+                        exclusion.hidden_user(users) == Some(record.user)
+                    })
+                })
+        })
+        .map(OverwriteRecord::revoke)
+        .collect()
+}
+
+/// compares the overwrites of all text and voice channels in the guild with
+/// the overwrites that should exist for the current exclusions.
+/// only the exclusions and overwrites of `source` are compared if it is given
+pub async fn compute_exclusion_changes(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: serenity::GuildId,
+    source: Option<ExclusionSource>,
+) -> Result<Vec<ExclusionChange>> {
+    let in_scope = |of: Option<ExclusionSource>| source.is_none() || of == source;
+    let exclusions = get_guild_exclusions(ctx, &data.database, guild_id)
+        .await?
+        .into_iter()
+        .filter(|exclusion| in_scope(exclusion.source()))
+        .collect::<Vec<Exclusion>>();
+    // the records of other sources are still needed to know who made an overwrite
+    let records = get_overwrite_records(&data.database, guild_id.into()).await?;
+    let scoped_records = records
+        .iter()
+        .filter(|record| in_scope(Some(record.source)))
+        .cloned()
+        .collect::<Vec<OverwriteRecord>>();
+    let mut changes = Vec::new();
+
+    // overwrites of exclusions that were removed or users that left a group
+    for record in scoped_records.iter() {
+        if !exclusions.iter().any(|exclusion| {
+            exclusion.source() == Some(record.source) && exclusion.ignores(record.user).is_some()
+        }) {
+            changes.push(record.revoke());
+        }
+    }
+
+    for (_, channel) in guild_id.channels(ctx.http()).await? {
+        let presence = channel_presence(ctx, &data.database, &data.text_policies, &channel).await?;
+        // users stay hidden in text channels only while the others are present
+        if is_excludable(&channel) && channel.is_text_based() {
+            changes.extend(text_channel_revokes(
+                channel.id.0,
+                presence.as_deref(),
+                &exclusions,
+                &scoped_records,
+            ));
+        }
+        let users = match presence {
+            Some(users) => users,
+            None => continue,
+        };
+        let permission = exclusion_permission(&channel);
+        for exclusion in exclusions.iter() {
+            let user = match exclusion.hidden_user(&users) {
+                Some(user) => user,
+                None => continue,
+            };
+            let channel_records = records
+                .iter()
+                .filter(|record| {
+                    record.channel_id == channel.id.0
+                        && record.user == user
+                        && record.permission == permission
+                })
+                .collect::<Vec<_>>();
+            let denied = channel.permission_overwrites.iter().any(|overwrite| {
+                overwrite.kind == serenity::PermissionOverwriteType::Member(user.into())
+                    && overwrite.deny.contains(permission)
+            });
+            // a denied overwrite without records was made by a moderator
+            let recorded = channel_records.is_empty()
+                || channel_records
+                    .iter()
+                    .any(|record| exclusion.source() == Some(record.source));
+            if !denied || !recorded {
+                changes.push(ExclusionChange::Deny {
+                    channel: channel.clone(),
+                    user,
+                    exclusion: exclusion.clone(),
+                });
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// applies changes computed by [`compute_exclusion_changes`]
+pub async fn apply_exclusion_changes(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: serenity::GuildId,
+    changes: &[ExclusionChange],
+) -> Result<()> {
+    let overwrites = guild_overwrites(ctx, &data.database, guild_id.into()).await?;
+    for change in changes {
+        match change {
+            ExclusionChange::Deny {
+                channel,
+                user,
+                exclusion,
+            } => {
+                exclude_user_from_channel(overwrites.as_ref(), data, channel, *user, exclusion)
+                    .await?
+            }
+            ExclusionChange::Revoke {
+                channel_id,
+                user,
+                source,
+                permission,
+            } => {
+                revoke_recorded_overwrite(
+                    ctx,
+                    overwrites.as_ref(),
+                    &data.database,
+                    *source,
+                    *channel_id,
+                    *user,
+                    *permission,
+                )
+                .await?
+            }
+        }
+    }
+    Ok(())
+}
+
+/// lifts the recorded overwrite of the source and removes its record
+async fn revoke_recorded_overwrite(
+    ctx: &serenity::Context,
+    overwrites: &dyn Overwrites,
+    database: &sqlx::SqlitePool,
+    source: ExclusionSource,
+    channel_id: u64,
+    user: u64,
+    permission: serenity::Permissions,
+) -> Result<()> {
+    revoke_overwrite(
+        ctx, overwrites, database, source, channel_id, user, permission,
+    )
+    .await?;
+    // simulated revokes keep the records of the real overwrites
+    if !overwrites.applies() {
+        return Ok(());
+    }
+    let (channel, user_id) = (channel_id as i64, user as i64);
+    let (kind, source_id) = (source.kind(), source.id());
+    sqlx::query!(
+        "DELETE FROM exclusion_overwrites WHERE source = ? AND source_id = ? AND channel_id = ? AND user_id = ?",
+        kind,
+        source_id,
+        channel,
+        user_id
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// revokes the overwrites in the text channels of the guild whose users aren't hidden anymore,
+/// like after the activity window passed or the policy changed
+pub async fn release_text_channels(
+    ctx: &serenity::Context,
+    database: &sqlx::SqlitePool,
+    policies: &GuildPolicies,
+    guild_id: serenity::GuildId,
+) -> Result<()> {
+    let records = get_overwrite_records(database, guild_id.into()).await?;
+    // text channels deny viewing the channel, voice channels deny connecting
+    let mut channels = records
+        .iter()
+        .filter(|record| record.permission == serenity::Permissions::VIEW_CHANNEL)
+        .map(|record| record.channel_id)
+        .collect::<Vec<u64>>();
+    channels.sort_unstable();
+    channels.dedup();
+    if channels.is_empty() {
+        return Ok(());
+    }
+    let exclusions = get_guild_exclusions(ctx, database, guild_id).await?;
+    let overwrites = guild_overwrites(ctx, database, guild_id.into()).await?;
+    for channel_id in channels {
+        // channel might have been deleted in the meantime
+        let channel = match serenity::ChannelId(channel_id).to_channel(ctx).await {
+            Ok(channel) => match channel.guild() {
+                Some(channel) => channel,
+                None => continue,
+            },
+            Err(_) => continue,
+        };
+        let presence = channel_presence(ctx, database, policies, &channel).await?;
+        for change in text_channel_revokes(channel_id, presence.as_deref(), &exclusions, &records) {
+            if let ExclusionChange::Revoke {
+                channel_id,
+                user,
+                source,
+                permission,
+            } = change
+            {
+                revoke_recorded_overwrite(
+                    ctx,
+                    overwrites.as_ref(),
+                    database,
+                    source,
+                    channel_id,
+                    user,
+                    permission,
+                )
+                .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// computes and applies all changes for the guild
+pub async fn sync_guild_exclusions(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: serenity::GuildId,
+) -> Result<()> {
+    let changes = compute_exclusion_changes(ctx, data, guild_id, None).await?;
+    apply_exclusion_changes(ctx, data, guild_id, &changes).await
+}
+
+/// computes and applies the changes of one exclusion group, for its current or former members
+pub async fn sync_group_exclusions(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: serenity::GuildId,
+    group_id: i64,
+) -> Result<()> {
+    let source = Some(ExclusionSource::Group(group_id));
+    let changes = compute_exclusion_changes(ctx, data, guild_id, source).await?;
+    apply_exclusion_changes(ctx, data, guild_id, &changes).await
+}
+
+/// only text and voice channels have members that can collide
+fn is_excludable(channel: &serenity::GuildChannel) -> bool {
+    matches!(
+        channel.kind,
+        serenity::ChannelType::Text
+            | serenity::ChannelType::News
+            | serenity::ChannelType::Voice
+            | serenity::ChannelType::Stage
+    )
+}
+
+/// denies the user access to the channel and records the overwrite for the exclusion,
+/// so it can be revoked once the exclusion is removed
+async fn exclude_user_from_channel(
+    overwrites: &dyn Overwrites,
+    data: &Data,
+    channel: &serenity::GuildChannel,
+    user: u64,
+    exclusion: &Exclusion,
+) -> Result<()> {
+    let source = exclusion
+        .source()
+        .ok_or_else(|| Error::InvalidInput("exclusion is not stored"))?;
+    let records = get_overwrite_records(&data.database, channel.guild_id.into()).await?;
+    let allowed = match deny_user(overwrites, channel, user, source, &records).await? {
+        Some(allowed) => allowed,
+        None => return Ok(()),
+    };
+    // simulated overwrites don't exist, so they are not recorded
+    if overwrites.applies() {
+        record_overwrite(&data.database, channel, user, source, allowed).await?;
+    }
+    Ok(())
+}
+
+/// denies the user access to the channel for the exclusion, without recording it.
+/// returns if the permission was allowed before, to restore the allow on revoke.
+/// `None` if there is nothing to record: the overwrite is recorded for the exclusion already
+/// or it was made by a moderator, who denied the permission without a record
+async fn deny_user(
+    overwrites: &dyn Overwrites,
+    channel: &serenity::GuildChannel,
+    user: u64,
+    source: ExclusionSource,
+    records: &[OverwriteRecord],
+) -> Result<Option<bool>> {
+    let permission = exclusion_permission(channel);
+    let kind = serenity::PermissionOverwriteType::Member(user.into());
+    let existing = channel
+        .permission_overwrites
+        .iter()
+        .find(|overwrite| overwrite.kind == kind);
+    let recorded = records
+        .iter()
+        .filter(|record| {
+            record.channel_id == channel.id.0
+                && record.user == user
+                && record.permission == permission
+        })
+        .collect::<Vec<_>>();
+
+    match existing {
+        Some(overwrite) if overwrite.deny.contains(permission) => {
+            if recorded.is_empty() || recorded.iter().any(|record| record.source == source) {
+                return Ok(None);
+            }
+            // the allow was removed by the exclusion that recorded the overwrite first
+            Ok(Some(recorded.iter().any(|record| record.allowed)))
+        }
+        _ => {
+            let (allow, deny) = existing.map_or(
+                (
+                    serenity::Permissions::empty(),
+                    serenity::Permissions::empty(),
+                ),
+                |overwrite| (overwrite.allow, overwrite.deny),
+            );
+            overwrites
+                .create(
+                    channel,
+                    serenity::PermissionOverwrite {
+                        allow: allow - permission,
+                        deny: deny | permission,
+                        kind,
+                    },
+                )
+                .await?;
+            Ok(Some(allow.contains(permission)))
+        }
+    }
+}
+
+/// records the overwrite of the user for the exclusion
+async fn record_overwrite(
+    database: &sqlx::SqlitePool,
+    channel: &serenity::GuildChannel,
+    user: u64,
+    source: ExclusionSource,
+    allowed: bool,
+) -> Result<()> {
+    let guild_id: i64 = channel.guild_id.into();
+    let channel_id: i64 = channel.id.into();
+    let (kind, source_id) = (source.kind(), source.id());
+    let user_id = user as i64;
+    let bits = exclusion_permission(channel).bits() as i64;
+    sqlx::query!(
+        "INSERT INTO exclusion_overwrites (source, source_id, guild_id, channel_id, user_id, permission, allowed) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        kind,
+        source_id,
+        guild_id,
+        channel_id,
+        user_id,
+        bits,
+        allowed,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// removes all overwrites that were applied because of the exclusion.
+/// overwrites that are still needed by another exclusion are kept
+pub async fn revoke_exclusion_overwrites(
+    ctx: &serenity::Context,
+    database: &sqlx::SqlitePool,
+    guild_id: i64,
+    exclusion: &Exclusion,
+) -> Result<()> {
+    let overwrites = guild_overwrites(ctx, database, guild_id).await?;
+    let source = exclusion
+        .source()
+        .ok_or_else(|| Error::InvalidInput("exclusion is not stored"))?;
+    let (kind, source_id) = (source.kind(), source.id());
+    let records = sqlx::query!(
+        "SELECT channel_id, user_id, permission FROM exclusion_overwrites WHERE source = ? AND source_id = ?",
+        kind,
+        source_id
+    )
+    .fetch_all(database)
+    .await?;
+    for record in records {
+        revoke_overwrite(
+            ctx,
+            overwrites.as_ref(),
+            database,
+            source,
+            record.channel_id as u64,
+            record.user_id as u64,
+            serenity::Permissions::from_bits_truncate(record.permission as u64),
+        )
+        .await?;
+    }
+    // simulated revokes keep the records of the real overwrites
+    if overwrites.applies() {
+        sqlx::query!(
+            "DELETE FROM exclusion_overwrites WHERE source = ? AND source_id = ?",
+            kind,
+            source_id
+        )
+        .execute(database)
+        .await?;
+    }
+    Ok(())
+}
+
+/// lifts the denied permission of the user in the channel
+/// if no other exclusion than `source` has a record for it
+async fn revoke_overwrite(
+    ctx: &serenity::Context,
+    overwrites: &dyn Overwrites,
+    database: &sqlx::SqlitePool,
+    source: ExclusionSource,
+    channel_id: u64,
+    user: u64,
+    permission: serenity::Permissions,
+) -> Result<()> {
+    let (channel, user_id, bits) = (channel_id as i64, user as i64, permission.bits() as i64);
+    let (kind, source_id) = (source.kind(), source.id());
+    let remaining = sqlx::query!(
+        "SELECT COUNT(*) AS count FROM exclusion_overwrites
+        WHERE channel_id = ? AND user_id = ? AND permission = ? AND NOT (source = ? AND source_id = ?)",
+        channel,
+        user_id,
+        bits,
+        kind,
+        source_id
+    )
+    .fetch_one(database)
+    .await?
+    .count;
+    if remaining > 0 {
+        return Ok(());
+    }
+    let allowed = sqlx::query!(
+        "SELECT allowed FROM exclusion_overwrites
+        WHERE channel_id = ? AND user_id = ? AND permission = ? AND source = ? AND source_id = ?",
+        channel,
+        user_id,
+        bits,
+        kind,
+        source_id
+    )
+    .fetch_optional(database)
+    .await?
+    .map_or(false, |record| record.allowed != 0);
+    // channel might have been deleted in the meantime
+    let channel = match serenity::ChannelId(channel_id).to_channel(ctx).await {
+        Ok(channel) => channel.guild(),
+        Err(_) => None,
+    };
+    if let Some(channel) = channel {
+        allow_user_in_channel(overwrites, &channel, user, permission, allowed).await?;
+    }
+    Ok(())
+}
+
+/// removes the denied permission from the member overwrite of the user and allows it again
+/// if it was allowed before. the overwrite is deleted completely if nothing else is left
+async fn allow_user_in_channel(
+    overwrites: &dyn Overwrites,
+    channel: &serenity::GuildChannel,
+    user: u64,
+    permission: serenity::Permissions,
+    allowed: bool,
+) -> Result<()> {
+    let kind = serenity::PermissionOverwriteType::Member(user.into());
+    let overwrite = match channel
+        .permission_overwrites
+        .iter()
+        .find(|overwrite| overwrite.kind == kind)
+    {
+        Some(overwrite) => overwrite,
+        None => return Ok(()),
+    };
+    let deny = overwrite.deny - permission;
+    let allow = if allowed {
+        overwrite.allow | permission
+    } else {
+        overwrite.allow
+    };
+    if deny.is_empty() && allow.is_empty() {
+        overwrites.delete(channel, kind).await?;
+    } else {
+        overwrites
+            .create(channel, serenity::PermissionOverwrite { allow, deny, kind })
+            .await?;
+    }
+    Ok(())
+}
+
+/// the permission that gets denied for excluded users
+fn exclusion_permission(channel: &serenity::GuildChannel) -> serenity::Permissions {
+    if channel.is_text_based() {
+        serenity::Permissions::VIEW_CHANNEL
+    } else {
+        serenity::Permissions::CONNECT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::overwrites::{OverwriteAction, RecordedOverwrites};
+
+    const CHANNEL: u64 = 20;
+
+    fn channel(kind: u8) -> serenity::GuildChannel {
+        serde_json::from_value(serde_json::json!({
+            "id": CHANNEL.to_string(),
+            "guild_id": "10",
+            "type": kind,
+            "name": "fixture",
+            "position": 0,
+            "permission_overwrites": [],
+            "nsfw": false,
+        }))
+        .expect("fixture channel")
+    }
+
+    fn text_channel() -> serenity::GuildChannel {
+        channel(0)
+    }
+
+    fn voice_channel() -> serenity::GuildChannel {
+        channel(2)
+    }
+
+    fn member_overwrite(
+        user: u64,
+        allow: serenity::Permissions,
+        deny: serenity::Permissions,
+    ) -> serenity::PermissionOverwrite {
+        serenity::PermissionOverwrite {
+            allow,
+            deny,
+            kind: serenity::PermissionOverwriteType::Member(user.into()),
+        }
+    }
+
+    fn pair(id: i64, users: (u64, u64)) -> Exclusion {
+        Exclusion {
+            id: Some(id),
+            ..Exclusion::from(users)
+        }
+    }
+
+    fn record(source: ExclusionSource, user: u64, allowed: bool) -> OverwriteRecord {
+        OverwriteRecord {
+            source,
+            channel_id: CHANNEL,
+            user,
+            permission: serenity::Permissions::VIEW_CHANNEL,
+            allowed,
+        }
+    }
+
+    fn created(overwrite: serenity::PermissionOverwrite) -> OverwriteAction {
+        OverwriteAction::Create {
+            channel_id: serenity::ChannelId(CHANNEL),
+            overwrite,
+        }
+    }
+
+    #[tokio::test]
+    async fn hides_the_partner_of_a_present_user() {
+        let overwrites = RecordedOverwrites::default();
+        let denied = deny_hidden_users(&overwrites, &text_channel(), &[1], &[pair(1, (1, 2))], &[])
+            .await
+            .unwrap();
+        assert_eq!(denied, vec![(2, ExclusionSource::Pair(1), false)]);
+        assert_eq!(
+            overwrites.actions(),
+            vec![created(member_overwrite(
+                2,
+                serenity::Permissions::empty(),
+                serenity::Permissions::VIEW_CHANNEL
+            ))]
+        );
+    }
+
+    #[tokio::test]
+    async fn denies_connecting_to_voice_channels() {
+        let overwrites = RecordedOverwrites::default();
+        deny_hidden_users(&overwrites, &voice_channel(), &[2], &[pair(1, (1, 2))], &[])
+            .await
+            .unwrap();
+        assert_eq!(
+            overwrites.actions(),
+            vec![created(member_overwrite(
+                1,
+                serenity::Permissions::empty(),
+                serenity::Permissions::CONNECT
+            ))]
+        );
+    }
+
+    #[tokio::test]
+    async fn one_way_exclusions_only_hide_the_ignoring_user() {
+        let exclusions = [pair(1, (1, 2)).with_direction(ExclusionDirection::OneWay(1))];
+        let overwrites = RecordedOverwrites::default();
+        let denied = deny_hidden_users(&overwrites, &text_channel(), &[1], &exclusions, &[])
+            .await
+            .unwrap();
+        assert!(denied.is_empty());
+        assert!(overwrites.actions().is_empty());
+
+        let denied = deny_hidden_users(&overwrites, &text_channel(), &[2], &exclusions, &[])
+            .await
+            .unwrap();
+        assert_eq!(denied, vec![(1, ExclusionSource::Pair(1), false)]);
+    }
+
+    #[tokio::test]
+    async fn leaves_overwrites_of_moderators_alone() {
+        let mut channel = text_channel();
+        channel.permission_overwrites.push(member_overwrite(
+            2,
+            serenity::Permissions::empty(),
+            serenity::Permissions::VIEW_CHANNEL,
+        ));
+        let overwrites = RecordedOverwrites::default();
+        let denied = deny_hidden_users(&overwrites, &channel, &[1], &[pair(1, (1, 2))], &[])
+            .await
+            .unwrap();
+        assert!(denied.is_empty());
+        assert!(overwrites.actions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn skips_overwrites_recorded_for_the_exclusion() {
+        let mut channel = text_channel();
+        channel.permission_overwrites.push(member_overwrite(
+            2,
+            serenity::Permissions::empty(),
+            serenity::Permissions::VIEW_CHANNEL,
+        ));
+        let records = [record(ExclusionSource::Pair(1), 2, false)];
+        let overwrites = RecordedOverwrites::default();
+        let denied = deny_hidden_users(&overwrites, &channel, &[1], &[pair(1, (1, 2))], &records)
+            .await
+            .unwrap();
+        assert!(denied.is_empty());
+        assert!(overwrites.actions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn records_overwrites_of_other_exclusions_without_changing_them() {
+        let mut channel = text_channel();
+        channel.permission_overwrites.push(member_overwrite(
+            2,
+            serenity::Permissions::empty(),
+            serenity::Permissions::VIEW_CHANNEL,
+        ));
+        let records = [record(ExclusionSource::Group(3), 2, true)];
+        let overwrites = RecordedOverwrites::default();
+        let denied = deny_hidden_users(&overwrites, &channel, &[1], &[pair(1, (1, 2))], &records)
+            .await
+            .unwrap();
+        assert_eq!(denied, vec![(2, ExclusionSource::Pair(1), true)]);
+        assert!(overwrites.actions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn removes_and_reports_an_allow_of_the_permission() {
+        let mut channel = text_channel();
+        channel.permission_overwrites.push(member_overwrite(
+            2,
+            serenity::Permissions::VIEW_CHANNEL | serenity::Permissions::SEND_MESSAGES,
+            serenity::Permissions::empty(),
+        ));
+        let overwrites = RecordedOverwrites::default();
+        let denied = deny_hidden_users(&overwrites, &channel, &[1], &[pair(1, (1, 2))], &[])
+            .await
+            .unwrap();
+        assert_eq!(denied, vec![(2, ExclusionSource::Pair(1), true)]);
+        assert_eq!(
+            overwrites.actions(),
+            vec![created(member_overwrite(
+                2,
+                serenity::Permissions::SEND_MESSAGES,
+                serenity::Permissions::VIEW_CHANNEL
+            ))]
+        );
+    }
+
+    #[tokio::test]
+    async fn denies_users_hidden_by_several_exclusions_once() {
+        let exclusions = [pair(1, (1, 2)), pair(2, (2, 3))];
+        let overwrites = RecordedOverwrites::default();
+        let denied = deny_hidden_users(&overwrites, &text_channel(), &[1, 3], &exclusions, &[])
+            .await
+            .unwrap();
+        assert_eq!(
+            denied,
+            vec![
+                (2, ExclusionSource::Pair(1), false),
+                (2, ExclusionSource::Pair(2), false)
+            ]
+        );
+        assert_eq!(overwrites.actions().len(), 1);
+    }
+}